@@ -448,6 +448,7 @@ impl ContextualActions for Actions {
             }
             ID::Car(_) => {
                 actions.push((Key::Backspace, "forcibly kill this car".to_string()));
+                actions.push((Key::N, "delete this car".to_string()));
                 actions.push((Key::G, "find front of blockage".to_string()));
             }
             ID::Area(_) => {
@@ -488,6 +489,14 @@ impl ContextualActions for Actions {
                 app.primary.current_selection = None;
                 Transition::Keep
             }
+            (ID::Car(c), "delete this car") => {
+                app.primary.sim.delete_car(c, &app.primary.map);
+                app.primary
+                    .sim
+                    .tiny_step(&app.primary.map, &mut app.primary.sim_cb);
+                app.primary.current_selection = None;
+                Transition::Keep
+            }
             (ID::Car(c), "find front of blockage") => Transition::Push(msg(
                 "Blockage results",
                 vec![format!(
@@ -16,7 +16,7 @@ use crate::helpers::ID;
 use crate::managed::{WrappedComposite, WrappedOutcome};
 use crate::render::{DrawIntersection, DrawLane, DrawRoad};
 use crate::sandbox::{GameplayMode, SandboxMode, TimeWarpScreen};
-use abstutil::Timer;
+use abstutil::{prettyprint_usize, Timer};
 use ezgui::{
     hotkey, lctrl, Btn, Choice, Color, Composite, Drawable, EventCtx, GfxCtx, HorizontalAlignment,
     Key, Line, Outcome, PersistentSplit, RewriteColor, ScreenRectangle, Text, TextExt,
@@ -102,6 +102,13 @@ impl EditMode {
                 } else {
                     app.primary.sim = old_sim;
                     app.primary.dirty_from_edits = true;
+                    let removed = app.primary.sim.handle_live_edits(&app.primary.map);
+                    if removed > 0 {
+                        timer.note(format!(
+                            "Removed {} cars that can't reach their goal after the edits",
+                            prettyprint_usize(removed)
+                        ));
+                    }
                     Transition::Pop
                 }
             } else {
@@ -111,7 +111,7 @@ pub fn people(ctx: &mut EventCtx, app: &App, details: &mut Details, id: Building
                     // TODO What to do here? This is meant for building callers right now
                     break;
                 }
-                TripResult::TripDone | TripResult::TripAborted => {}
+                TripResult::TripDone | TripResult::TripAborted | TripResult::TripCancelled => {}
                 TripResult::TripDoesntExist | TripResult::RemoteTrip => unreachable!(),
             }
         }
@@ -115,6 +115,11 @@ pub fn trips(
                     open_trips.get(t).map(|_| trip::aborted(ctx, app, *t)),
                 )
             }
+            TripResult::TripCancelled => (
+                "cancelled",
+                Color::hex("#A3A3A3"),
+                open_trips.get(t).map(|_| Widget::nothing()),
+            ),
             TripResult::TripDoesntExist => unreachable!(),
         };
         let (_, _, _, trip_mode) = sim.trip_info(*t);
@@ -236,6 +241,9 @@ pub fn bio(
     let mut table = vec![("Nickname", nickname), ("Age", age.to_string())];
     if app.opts.dev {
         table.push(("Debug ID", format!("{:?}", person.orig_id)));
+        if let Some(ref provenance) = person.provenance {
+            table.push(("Spawned by", provenance.clone()));
+        }
     }
     rows.extend(make_table(ctx, table));
     // TODO Mad libs!
@@ -439,12 +447,16 @@ pub fn parked_car(
 
     // TODO how long idle, prev trips, next trips, etc
 
-    let p = app.primary.sim.get_owner_of_car(id).unwrap();
-    rows.push(Btn::text_bg2(format!("Owned by {}", p)).build_def(ctx, None));
-    details.hyperlinks.insert(
-        format!("Owned by {}", p),
-        Tab::PersonTrips(p, BTreeMap::new()),
-    );
+    if let Some(p) = app.primary.sim.get_owner_of_car(id) {
+        rows.push(Btn::text_bg2(format!("Owned by {}", p)).build_def(ctx, None));
+        details.hyperlinks.insert(
+            format!("Owned by {}", p),
+            Tab::PersonTrips(p, BTreeMap::new()),
+        );
+    } else {
+        // Seeded parked cars that nobody will ever drive
+        rows.push("Not owned by anybody".draw_text(ctx));
+    }
 
     if let Some(p) = app.primary.sim.lookup_parked_car(id) {
         match p.spot {
@@ -65,7 +65,11 @@ pub fn ongoing(
         },
         AgentID::BusPassenger(_, _) => "riding the bus",
     };
-    let time_so_far = app.primary.sim.time() - start_time;
+    let time_so_far = app
+        .primary
+        .sim
+        .get_trip_elapsed(agent)
+        .unwrap_or_else(|| app.primary.sim.time() - start_time);
 
     let mut col = Vec::new();
 
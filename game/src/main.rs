@@ -35,6 +35,9 @@ fn main() {
     };
     let mut opts = options::Options::default();
     opts.dev = args.enabled("--dev");
+    if let Some(dt) = args.optional_parse("--time_increment", geom::Duration::parse) {
+        opts.time_increment = dt;
+    }
     if args.enabled("--lowzoom") {
         opts.min_zoom_for_detail = 1.0;
     }
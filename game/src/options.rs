@@ -19,6 +19,7 @@ pub struct Options {
 
     pub time_increment: Duration,
     pub resume_after_edit: bool,
+    pub pause_when_unfocused: bool,
 }
 
 impl Options {
@@ -34,6 +35,7 @@ impl Options {
 
             time_increment: Duration::minutes(10),
             resume_after_edit: true,
+            pause_when_unfocused: true,
         }
     }
 }
@@ -65,6 +67,13 @@ impl OptionsPanel {
                     .margin_below(10),
                     Checkbox::text(ctx, "Enable developer mode", None, app.opts.dev)
                         .margin_below(10),
+                    Checkbox::text(
+                        ctx,
+                        "Pause the simulation when the window loses focus",
+                        None,
+                        app.opts.pause_when_unfocused,
+                    )
+                    .margin_below(10),
                     "Camera controls".draw_text(ctx).margin_below(10),
                     Widget::col(vec![
                         Checkbox::text(
@@ -216,6 +225,9 @@ impl State for OptionsPanel {
                 }
                 "Apply" => {
                     app.opts.dev = self.composite.is_checked("Enable developer mode");
+                    app.opts.pause_when_unfocused = self
+                        .composite
+                        .is_checked("Pause the simulation when the window loses focus");
 
                     ctx.canvas.invert_scroll = self
                         .composite
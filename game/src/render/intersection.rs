@@ -141,12 +141,14 @@ impl Renderable for DrawIntersection {
                 .map(|(t, _)| *t != app.primary.sim.time())
                 .unwrap_or(true);
             if recalc {
-                let (idx, phase, t) =
-                    signal.current_phase_and_remaining_time(app.primary.sim.time());
+                let (idx, t) = app
+                    .primary
+                    .sim
+                    .current_signal_phase(self.id, &app.primary.map);
                 let mut batch = GeomBatch::new();
                 draw_signal_phase(
                     g.prerender,
-                    phase,
+                    &signal.phases[idx],
                     self.id,
                     Some(t),
                     &mut batch,
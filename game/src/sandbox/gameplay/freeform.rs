@@ -19,7 +19,7 @@ use rand::seq::SliceRandom;
 use rand::Rng;
 use sim::{
     DontDrawAgents, DrivingGoal, IndividTrip, PersonID, PersonSpec, Scenario, SidewalkSpot,
-    SpawnTrip, TripEndpoint, TripMode, TripSpec,
+    SpawnTrip, TripEndpoint, TripMode, TripPurpose, TripSpec,
 };
 
 // TODO Maybe remember what things were spawned, offer to replay this later
@@ -272,6 +272,8 @@ impl State for AgentSpawner {
                         scenario.people.push(PersonSpec {
                             id: PersonID(app.primary.sim.get_all_people().len() + i),
                             orig_id: None,
+                            provenance: None,
+                            label: None,
                             trips: vec![IndividTrip {
                                 depart: app.primary.sim.time(),
                                 trip: SpawnTrip::new(
@@ -280,6 +282,7 @@ impl State for AgentSpawner {
                                     self.composite.dropdown_value("mode"),
                                     map,
                                 ),
+                                purpose: TripPurpose::Unknown,
                             }],
                         });
                     }
@@ -544,8 +547,10 @@ pub fn spawn_agents_around(i: IntersectionID, app: &mut App) {
                         use_vehicle: person.vehicles[0].id,
                         retry_if_no_room: false,
                         origin: None,
+                        give_up_at: None,
                     },
                     TripEndpoint::Border(lane.src_i, None),
+                    TripPurpose::Unknown,
                     map,
                 );
             }
@@ -566,6 +571,7 @@ pub fn spawn_agents_around(i: IntersectionID, app: &mut App) {
                         ),
                     },
                     TripEndpoint::Border(lane.src_i, None),
+                    TripPurpose::Unknown,
                     map,
                 );
             }
@@ -21,7 +21,8 @@ use map_model::raw::{OriginalIntersection, OriginalRoad};
 use map_model::{BuildingID, Map, OriginalLane, Position};
 use sim::{
     AgentID, Analytics, BorderSpawnOverTime, CarID, DrivingGoal, IndividTrip, OriginDestination,
-    PersonID, PersonSpec, Scenario, ScenarioGenerator, SpawnOverTime, SpawnTrip, VehicleType,
+    OriginWeighting, PersonID, PersonSpec, Scenario, ScenarioGenerator, SpawnOverTime, SpawnTrip,
+    TimeDistribution, TripPurpose, VehicleType,
 };
 use std::collections::BTreeSet;
 
@@ -642,10 +643,17 @@ fn make_bike_lane_scenario(map: &Map) -> ScenarioGenerator {
         percent_use_transit: 0.0,
         start_time: Time::START_OF_DAY,
         stop_time: Time::START_OF_DAY + Duration::seconds(10.0),
+        distribution: TimeDistribution::Uniform,
         start_from_border: map
             .find_r_by_osm_id(263665925, (2499826475, 53096959))
             .unwrap()
             .backwards(),
+        start_from_borders: Vec::new(),
+        permanent_borders: Vec::new(),
+        platoon_size: None,
+        metering: false,
+        nearest_border_fallback: false,
+        purpose: TripPurpose::Unknown,
         goal: OriginDestination::GotoBldg(map.find_b_by_osm_id(217699501).unwrap()),
     });
     s
@@ -1087,6 +1095,8 @@ impl TutorialState {
                     scenario.people.push(PersonSpec {
                         id: PersonID(0),
                         orig_id: None,
+                        provenance: None,
+                        label: None,
                         trips: vec![IndividTrip {
                             depart: Time::START_OF_DAY,
                             trip: SpawnTrip::VehicleAppearing {
@@ -1096,7 +1106,10 @@ impl TutorialState {
                                 ),
                                 goal: DrivingGoal::ParkNear(goal_bldg),
                                 is_bike: false,
+                                origin: None,
+                                give_up_at: None,
                             },
+                            purpose: TripPurpose::Unknown,
                         }],
                     });
                     // Will definitely get there first
@@ -1104,6 +1117,8 @@ impl TutorialState {
                         scenario.people.push(PersonSpec {
                             id: PersonID(i + 1),
                             orig_id: None,
+                            provenance: None,
+                            label: None,
                             trips: vec![IndividTrip {
                                 depart: Time::START_OF_DAY,
                                 trip: SpawnTrip::VehicleAppearing {
@@ -1113,7 +1128,10 @@ impl TutorialState {
                                     ),
                                     goal: DrivingGoal::ParkNear(goal_bldg),
                                     is_bike: false,
+                                    origin: None,
+                                    give_up_at: None,
                                 },
+                                purpose: TripPurpose::Unknown,
                             }],
                         });
                     }
@@ -1179,8 +1197,16 @@ impl TutorialState {
                         only_seed_buses: Some(BTreeSet::new()),
                         spawn_over_time: vec![SpawnOverTime {
                             num_agents: 1000,
+                            extra_agent_chance: 0.0,
                             start_time: Time::START_OF_DAY,
                             stop_time: Time::START_OF_DAY + Duration::hours(3),
+                            distribution: TimeDistribution::Uniform,
+                            jitter: None,
+                            goal_retries: 0,
+                            origin_weighting: OriginWeighting::Uniform,
+                            nearest_border_fallback: false,
+                            purpose: TripPurpose::Unknown,
+                            origin_weights: Vec::new(),
                             goal: OriginDestination::Anywhere,
                             percent_driving: 1.0,
                             percent_biking: 0.0,
@@ -1198,7 +1224,7 @@ impl TutorialState {
                         &app.primary.map,
                         &mut app.primary.current_flags.sim_flags.make_rng(),
                         &mut Timer::throwaway(),
-                    )
+                    );
                 }))
                 .msg(
                     vec![
@@ -66,19 +66,22 @@ pub struct ShowTrafficSignal {
     i: IntersectionID,
     composite: Composite,
     current_phase: usize,
+    // Only in dev mode, for making the simulation hold a phase
+    debug_panel: Option<Composite>,
 }
 
 impl ShowTrafficSignal {
     pub fn new(ctx: &mut EventCtx, app: &App, i: IntersectionID) -> Box<dyn State> {
-        let (idx, _, _) = app
-            .primary
-            .map
-            .get_traffic_signal(i)
-            .current_phase_and_remaining_time(app.primary.sim.time());
+        let (idx, _) = app.primary.sim.current_signal_phase(i, &app.primary.map);
         return Box::new(ShowTrafficSignal {
             i,
             composite: make_signal_diagram(ctx, app, i, idx, false),
             current_phase: idx,
+            debug_panel: if app.opts.dev {
+                Some(make_signal_debug_panel(ctx, app, i, idx))
+            } else {
+                None
+            },
         });
     }
 
@@ -88,6 +91,18 @@ impl ShowTrafficSignal {
             self.composite = make_signal_diagram(ctx, app, self.i, self.current_phase, false);
             self.composite
                 .scroll_to_member(ctx, format!("phase {}", idx + 1));
+            self.rebuild_debug_panel(ctx, app);
+        }
+    }
+
+    fn rebuild_debug_panel(&mut self, ctx: &mut EventCtx, app: &App) {
+        if self.debug_panel.is_some() {
+            self.debug_panel = Some(make_signal_debug_panel(
+                ctx,
+                app,
+                self.i,
+                self.current_phase,
+            ));
         }
     }
 }
@@ -107,6 +122,24 @@ impl State for ShowTrafficSignal {
             self.change_phase(self.current_phase + 1, ctx, app);
         }
 
+        // For debugging gridlock, make the simulation hold the phase being shown
+        if let Some(Outcome::Clicked(x)) = self.debug_panel.as_mut().and_then(|p| p.event(ctx)) {
+            match x.as_ref() {
+                "hold this phase" => {
+                    app.primary
+                        .sim
+                        .override_signal(self.i, self.current_phase, &app.primary.map);
+                }
+                "stop holding" => {
+                    app.primary
+                        .sim
+                        .clear_signal_override(self.i, &app.primary.map);
+                }
+                _ => unreachable!(),
+            }
+            self.rebuild_debug_panel(ctx, app);
+        }
+
         match self.composite.event(ctx) {
             Some(Outcome::Clicked(x)) => match x.as_ref() {
                 "close" => {
@@ -143,7 +176,44 @@ impl State for ShowTrafficSignal {
         batch.draw(g);
 
         self.composite.draw(g);
+        if let Some(ref panel) = self.debug_panel {
+            panel.draw(g);
+        }
+    }
+}
+
+fn make_signal_debug_panel(
+    ctx: &mut EventCtx,
+    app: &App,
+    i: IntersectionID,
+    current_phase: usize,
+) -> Composite {
+    let mut col = Vec::new();
+    if let Some(idx) = app.primary.sim.get_signal_override(i) {
+        col.push(
+            Line(format!("The simulation is holding phase {}", idx + 1))
+                .draw(ctx)
+                .margin_below(5),
+        );
+        if idx != current_phase {
+            col.push(
+                Btn::text_fg("hold this phase")
+                    .build_def(ctx, hotkey(Key::H))
+                    .margin_below(5),
+            );
+        }
+        col.push(Btn::text_fg("stop holding").build_def(ctx, hotkey(Key::C)));
+    } else {
+        col.push(
+            Line("The simulation is running this signal normally")
+                .draw(ctx)
+                .margin_below(5),
+        );
+        col.push(Btn::text_fg("hold this phase").build_def(ctx, hotkey(Key::H)));
     }
+    Composite::new(Widget::col(col).padding(10).bg(app.cs.panel_bg))
+        .aligned(HorizontalAlignment::Right, VerticalAlignment::Top)
+        .build(ctx)
 }
 
 pub struct TurnExplorer {
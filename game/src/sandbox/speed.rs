@@ -1,23 +1,80 @@
 use crate::app::{App, FindDelayedIntersections};
 use crate::common::Warping;
-use crate::game::{msg, State, Transition};
+use crate::game::{msg, State, Transition, WizardState};
 use crate::helpers::ID;
 use crate::sandbox::{GameplayMode, SandboxMode};
-use abstutil::prettyprint_usize;
+use abstutil::{prettyprint_usize, Timer};
 use ezgui::{
-    hotkey, AreaSlider, Btn, Choice, Color, Composite, EventCtx, EventLoopMode, GeomBatch, GfxCtx,
-    HorizontalAlignment, Key, Line, Outcome, PersistentSplit, RewriteColor, Text,
-    VerticalAlignment, Widget,
+    hotkey, AreaSlider, Btn, Checkbox, Choice, Color, Composite, EventCtx, EventLoopMode,
+    GeomBatch, GfxCtx, HorizontalAlignment, Key, Line, Outcome, PersistentSplit, RewriteColor,
+    Text, VerticalAlignment, Widget, Wizard,
 };
 use geom::{Duration, Polygon, Pt2D, Time};
 use instant::Instant;
-use sim::AlertLocation;
+use sim::{AlertLocation, CarID, Sim};
+use std::collections::VecDeque;
+
+// Rewinding is only possible in dev mode, since copying the sim regularly is expensive.
+const SNAPSHOT_FREQUENCY: Duration = Duration::const_seconds(60.0);
+// At high speeds, a sim-minute passes every frame or so. Don't copy the sim more often than this,
+// in real time.
+const MIN_REAL_TIME_BETWEEN_SNAPSHOTS: Duration = Duration::const_seconds(5.0);
+const MAX_SNAPSHOTS: usize = 10;
+const REWIND_BY: Duration = Duration::const_seconds(10.0);
+// Stepping backwards replays from a snapshot, so only do it when that's not much work
+const MAX_STEP_BACK_REPLAY: Duration = Duration::const_seconds(120.0);
+// Stepping by this much happens immediately, without a TimeWarpScreen
+const TINY_STEP: Duration = Duration::const_seconds(0.1);
+// How often to measure the real speed, in real time
+const MEASUREMENT_FREQUENCY: Duration = Duration::const_seconds(1.0);
+const MAX_MEASUREMENTS: usize = 30;
+// Below this fraction of the requested speed, the sim isn't keeping up
+const KEEPING_UP: f64 = 0.9;
+// Cars blocked this long count as gridlock
+const GRIDLOCK_THRESHOLD: Duration = Duration::const_seconds(300.0);
+// Cars blocked this long are visited by "next stuck car"
+const STUCK_THRESHOLD: Duration = Duration::const_seconds(60.0);
 
 pub struct SpeedControls {
     pub composite: Composite,
 
     paused: bool,
     setting: SpeedSetting,
+    // No setting is faster than this, in sim seconds per real second
+    speed_cap: f64,
+    // Checked after every step while running
+    pause_condition: Option<PauseCondition>,
+    // Oldest first
+    snapshots: VecDeque<Sim>,
+    // When the newest snapshot was taken, in real time
+    last_snapshot: Option<Instant>,
+    // The fraction of the requested speed actually achieved, oldest first
+    measurements: VecDeque<f64>,
+    // (real time, sim time) elapsed since the last measurement
+    unmeasured: (Duration, Duration),
+    // The last car visited by "next stuck car"
+    last_stuck_car: Option<CarID>,
+}
+
+// When this becomes true, the simulation pauses and the reason is shown. It's only used once.
+pub struct PauseCondition {
+    pub reason: PauseReason,
+    pub check: Box<dyn Fn(&Sim) -> bool>,
+}
+
+pub enum PauseReason {
+    // From the "pause on gridlock" checkbox
+    Gridlock,
+    Other(String),
+}
+
+impl PauseReason {
+    fn describe(&self) -> String {
+        match self {
+            PauseReason::Gridlock => "Some cars have been stuck for 5 minutes.".to_string(),
+            PauseReason::Other(reason) => reason.clone(),
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, PartialOrd)]
@@ -30,11 +87,67 @@ enum SpeedSetting {
     Faster,
     // 1 sim hour per real second
     Fastest,
+    // Typed in exactly, in sim seconds per real second
+    Custom(f64),
+}
+
+// Everything except Custom, slowest first
+const PRESETS: [SpeedSetting; 4] = [
+    SpeedSetting::Realtime,
+    SpeedSetting::Fast,
+    SpeedSetting::Faster,
+    SpeedSetting::Fastest,
+];
+
+impl SpeedSetting {
+    fn multiplier(self) -> f64 {
+        match self {
+            SpeedSetting::Realtime => 1.0,
+            SpeedSetting::Fast => 5.0,
+            SpeedSetting::Faster => 30.0,
+            SpeedSetting::Fastest => 3600.0,
+            SpeedSetting::Custom(x) => x,
+        }
+    }
+
+    // The fastest speed allowed is a custom one at the cap, if no preset matches it
+    fn clamp(self, cap: f64) -> SpeedSetting {
+        if self.multiplier() <= cap {
+            return self;
+        }
+        PRESETS
+            .iter()
+            .find(|s| s.multiplier() == cap)
+            .cloned()
+            .unwrap_or(SpeedSetting::Custom(cap))
+    }
+
+    fn slower(self) -> Option<SpeedSetting> {
+        PRESETS
+            .iter()
+            .rev()
+            .find(|s| s.multiplier() < self.multiplier())
+            .cloned()
+    }
+
+    fn faster(self, cap: f64) -> Option<SpeedSetting> {
+        PRESETS
+            .iter()
+            .find(|s| s.multiplier() > self.multiplier() && s.multiplier() <= cap)
+            .cloned()
+    }
 }
 
 impl SpeedControls {
     // TODO Could use checkbox here, but not sure it'll make things that much simpler.
-    fn make_panel(ctx: &mut EventCtx, app: &App, paused: bool, setting: SpeedSetting) -> Composite {
+    fn make_panel(
+        ctx: &mut EventCtx,
+        app: &App,
+        paused: bool,
+        setting: SpeedSetting,
+        speed_cap: f64,
+        pause_on_gridlock: bool,
+    ) -> Composite {
         let mut row = Vec::new();
         row.push(
             if paused {
@@ -59,13 +172,14 @@ impl SpeedControls {
                     (SpeedSetting::Fastest, "3600x speed"),
                 ]
                 .into_iter()
+                .filter(|(s, _)| s.multiplier() <= speed_cap)
                 .map(|(s, label)| {
                     let mut txt = Text::from(Line(label).small());
                     txt.extend(Text::tooltip(ctx, hotkey(Key::LeftArrow), "slow down"));
                     txt.extend(Text::tooltip(ctx, hotkey(Key::RightArrow), "speed up"));
 
                     Btn::svg_def("../data/system/assets/speed/triangle.svg")
-                        .normal_color(if setting >= s {
+                        .normal_color(if setting.multiplier() >= s.multiplier() {
                             RewriteColor::NoOp
                         } else {
                             RewriteColor::ChangeAll(Color::WHITE.alpha(0.2))
@@ -81,6 +195,37 @@ impl SpeedControls {
             .padding(6)
             .margin_right(16),
         );
+        row.push(
+            if let SpeedSetting::Custom(x) = setting {
+                Btn::text_fg(format!("{}x", x))
+            } else {
+                Btn::text_fg("custom")
+            }
+            .build(ctx, "custom speed", None)
+            .centered_vert()
+            .margin_right(16),
+        );
+        if app.opts.dev {
+            row.push(
+                Btn::text_fg(format!("max {}x", speed_cap))
+                    .build(ctx, "change speed cap", None)
+                    .centered_vert()
+                    .margin_right(16),
+            );
+        }
+        row.push(
+            Checkbox::text(ctx, "pause on gridlock", None, pause_on_gridlock)
+                .centered_vert()
+                .margin_right(16),
+        );
+        row.push(
+            Btn::text_fg("next stuck car")
+                .build_def(ctx, hotkey(Key::G))
+                .centered_vert()
+                .margin_right(16),
+        );
+        // Filled in after the first measurement
+        row.push(Widget::nothing().named("measured speed"));
 
         row.push(
             PersistentSplit::new(
@@ -88,28 +233,36 @@ impl SpeedControls {
                 "step forwards",
                 app.opts.time_increment,
                 hotkey(Key::M),
-                vec![
-                    Choice::new("+1h", Duration::hours(1)),
-                    Choice::new("+30m", Duration::minutes(30)),
-                    Choice::new("+10m", Duration::minutes(10)),
-                    Choice::new("+0.1s", Duration::seconds(0.1)),
-                ],
+                step_choices(app.opts.time_increment),
             )
             .bg(app.cs.section_bg)
             .margin_right(16),
         );
 
-        row.push(
-            Widget::row(vec![
-                Btn::svg_def("../data/system/assets/speed/jump_to_time.svg")
-                    .pad(9)
-                    .build(ctx, "jump to specific time", hotkey(Key::B)),
-                Btn::svg_def("../data/system/assets/speed/reset.svg")
-                    .pad(9)
-                    .build(ctx, "reset to midnight", hotkey(Key::X)),
-            ])
-            .bg(app.cs.section_bg),
+        let mut time_btns = Vec::new();
+        if app.opts.dev {
+            time_btns.push(
+                Btn::text_fg("rewind 10s")
+                    .build_def(ctx, None)
+                    .centered_vert(),
+            );
+            time_btns.push(
+                Btn::text_fg("step back 0.1s")
+                    .build_def(ctx, None)
+                    .centered_vert(),
+            );
+        }
+        time_btns.push(
+            Btn::svg_def("../data/system/assets/speed/jump_to_time.svg")
+                .pad(9)
+                .build(ctx, "jump to specific time", hotkey(Key::B)),
+        );
+        time_btns.push(
+            Btn::svg_def("../data/system/assets/speed/reset.svg")
+                .pad(9)
+                .build(ctx, "reset to midnight", hotkey(Key::X)),
         );
+        row.push(Widget::row(time_btns).bg(app.cs.section_bg));
 
         Composite::new(Widget::row(row).bg(app.cs.panel_bg).padding(16))
             .aligned(
@@ -120,50 +273,107 @@ impl SpeedControls {
     }
 
     pub fn new(ctx: &mut EventCtx, app: &App) -> SpeedControls {
-        let composite = SpeedControls::make_panel(ctx, app, false, SpeedSetting::Realtime);
+        let speed_cap = SpeedSetting::Fastest.multiplier();
+        let composite =
+            SpeedControls::make_panel(ctx, app, false, SpeedSetting::Realtime, speed_cap, false);
         SpeedControls {
             composite,
             paused: false,
             setting: SpeedSetting::Realtime,
+            speed_cap,
+            pause_condition: None,
+            snapshots: VecDeque::new(),
+            last_snapshot: None,
+            measurements: VecDeque::new(),
+            unmeasured: (Duration::ZERO, Duration::ZERO),
+            last_stuck_car: None,
         }
     }
 
+    // Call after changing anything the panel shows
+    fn rebuild_panel(&mut self, ctx: &mut EventCtx, app: &App) {
+        self.composite = SpeedControls::make_panel(
+            ctx,
+            app,
+            self.paused,
+            self.setting,
+            self.speed_cap,
+            self.pausing_on_gridlock(),
+        );
+    }
+
     pub fn event(
         &mut self,
         ctx: &mut EventCtx,
         app: &mut App,
         maybe_mode: Option<&GameplayMode>,
     ) -> Option<Transition> {
+        // Don't race ahead while nobody's watching. Resuming is up to the user.
+        if app.opts.pause_when_unfocused && ctx.input.window_lost_cursor() {
+            self.pause(ctx, app);
+        }
+
         match self.composite.event(ctx) {
             Some(Outcome::Clicked(x)) => match x.as_ref() {
                 "real-time speed" => {
                     self.setting = SpeedSetting::Realtime;
-                    self.composite = SpeedControls::make_panel(ctx, app, self.paused, self.setting);
+                    self.rebuild_panel(ctx, app);
                     return None;
                 }
                 "5x speed" => {
                     self.setting = SpeedSetting::Fast;
-                    self.composite = SpeedControls::make_panel(ctx, app, self.paused, self.setting);
+                    self.rebuild_panel(ctx, app);
                     return None;
                 }
                 "30x speed" => {
                     self.setting = SpeedSetting::Faster;
-                    self.composite = SpeedControls::make_panel(ctx, app, self.paused, self.setting);
+                    self.rebuild_panel(ctx, app);
                     return None;
                 }
                 "3600x speed" => {
                     self.setting = SpeedSetting::Fastest;
-                    self.composite = SpeedControls::make_panel(ctx, app, self.paused, self.setting);
+                    self.rebuild_panel(ctx, app);
                     return None;
                 }
+                "custom speed" => {
+                    let cap = self.speed_cap;
+                    return Some(Transition::Push(WizardState::new(Box::new(
+                        move |wiz, ctx, app| choose_custom_speed(wiz, ctx, app, cap),
+                    ))));
+                }
+                "change speed cap" => {
+                    return Some(Transition::Push(WizardState::new(Box::new(
+                        choose_speed_cap,
+                    ))));
+                }
                 "play" => {
                     self.paused = false;
-                    self.composite = SpeedControls::make_panel(ctx, app, self.paused, self.setting);
+                    self.unmeasured = (Duration::ZERO, Duration::ZERO);
+                    self.rebuild_panel(ctx, app);
                     return None;
                 }
                 "pause" => {
                     self.pause(ctx, app);
                 }
+                "next stuck car" => {
+                    let sim = &app.primary.sim;
+                    if let Some(car) = sim.next_stuck_agent(STUCK_THRESHOLD, self.last_stuck_car) {
+                        self.last_stuck_car = Some(car);
+                        let id = ID::Car(car);
+                        return Some(Transition::Push(Warping::new(
+                            ctx,
+                            id.canonical_point(&app.primary).unwrap(),
+                            Some(10.0),
+                            Some(id),
+                            &mut app.primary,
+                        )));
+                    }
+                    self.last_stuck_car = None;
+                    return Some(Transition::Push(msg(
+                        "No stuck cars",
+                        vec!["Nobody's been blocked for a minute."],
+                    )));
+                }
                 "reset to midnight" => {
                     if let Some(mode) = maybe_mode {
                         return Some(Transition::Replace(Box::new(SandboxMode::new(
@@ -178,16 +388,66 @@ impl SpeedControls {
                         )));
                     }
                 }
+                "rewind 10s" => {
+                    let now = app.primary.sim.time();
+                    if let Some(idx) = self
+                        .snapshots
+                        .iter()
+                        .rposition(|sim| now - sim.time() >= REWIND_BY)
+                    {
+                        // The later snapshots are from the future now. Keep this one around, in
+                        // case we want to rewind again.
+                        self.snapshots.truncate(idx + 1);
+                        let sim = self.snapshots[idx].clone();
+                        return Some(self.rewind_to(ctx, app, maybe_mode, sim));
+                    }
+                    return Some(Transition::Push(msg(
+                        "Error",
+                        vec!["There's no savestate from far enough back to rewind to."],
+                    )));
+                }
+                "step back 0.1s" => {
+                    let now = app.primary.sim.time();
+                    if now - Time::START_OF_DAY < TINY_STEP {
+                        return Some(Transition::Push(msg(
+                            "Error",
+                            vec!["Can't step back before midnight."],
+                        )));
+                    }
+                    let target = now - TINY_STEP;
+                    if let Some(snapshot) = self
+                        .snapshots
+                        .iter()
+                        .rev()
+                        .find(|sim| sim.time() <= target)
+                        .filter(|sim| target - sim.time() <= MAX_STEP_BACK_REPLAY)
+                    {
+                        let mut sim = snapshot.clone();
+                        let dt = target - sim.time();
+                        sim.timed_step(
+                            &app.primary.map,
+                            dt,
+                            &mut app.primary.sim_cb,
+                            &mut Timer::throwaway(),
+                        );
+                        return Some(self.rewind_to(ctx, app, maybe_mode, sim));
+                    }
+                    return Some(Transition::Push(msg(
+                        "Error",
+                        vec!["There's no recent savestate to replay from."],
+                    )));
+                }
                 "jump to specific time" => {
                     return Some(Transition::Push(Box::new(JumpToTime::new(
                         ctx,
                         app,
                         maybe_mode.cloned(),
+                        (self.paused, self.setting),
                     ))));
                 }
                 "step forwards" => {
                     let dt = self.composite.persistent_split_value("step forwards");
-                    if dt == Duration::seconds(0.1) {
+                    if dt == TINY_STEP {
                         app.primary
                             .sim
                             .tiny_step(&app.primary.map, &mut app.primary.sim_cb);
@@ -207,59 +467,46 @@ impl SpeedControls {
         }
         // Just kind of constantly scrape this
         app.opts.time_increment = self.composite.persistent_split_value("step forwards");
+        let pause_on_gridlock = self.composite.is_checked("pause on gridlock");
+        if pause_on_gridlock != self.pausing_on_gridlock() {
+            if pause_on_gridlock {
+                self.set_pause_condition(
+                    ctx,
+                    app,
+                    PauseCondition {
+                        reason: PauseReason::Gridlock,
+                        check: Box::new(|sim| !sim.get_stuck_agents(GRIDLOCK_THRESHOLD).is_empty()),
+                    },
+                );
+            } else {
+                self.pause_condition = None;
+            }
+        }
 
         if ctx.input.new_was_pressed(&hotkey(Key::LeftArrow).unwrap()) {
-            match self.setting {
-                SpeedSetting::Realtime => self.pause(ctx, app),
-                SpeedSetting::Fast => {
-                    self.setting = SpeedSetting::Realtime;
-                    self.composite = SpeedControls::make_panel(ctx, app, self.paused, self.setting);
-                }
-                SpeedSetting::Faster => {
-                    self.setting = SpeedSetting::Fast;
-                    self.composite = SpeedControls::make_panel(ctx, app, self.paused, self.setting);
-                }
-                SpeedSetting::Fastest => {
-                    self.setting = SpeedSetting::Faster;
-                    self.composite = SpeedControls::make_panel(ctx, app, self.paused, self.setting);
-                }
+            if let Some(setting) = self.setting.slower() {
+                self.setting = setting;
+                self.rebuild_panel(ctx, app);
+            } else {
+                self.pause(ctx, app);
             }
         }
         if ctx.input.new_was_pressed(&hotkey(Key::RightArrow).unwrap()) {
-            match self.setting {
-                SpeedSetting::Realtime => {
-                    if self.paused {
-                        self.paused = false;
-                        self.composite =
-                            SpeedControls::make_panel(ctx, app, self.paused, self.setting);
-                    } else {
-                        self.setting = SpeedSetting::Fast;
-                        self.composite =
-                            SpeedControls::make_panel(ctx, app, self.paused, self.setting);
-                    }
-                }
-                SpeedSetting::Fast => {
-                    self.setting = SpeedSetting::Faster;
-                    self.composite = SpeedControls::make_panel(ctx, app, self.paused, self.setting);
-                }
-                SpeedSetting::Faster => {
-                    self.setting = SpeedSetting::Fastest;
-                    self.composite = SpeedControls::make_panel(ctx, app, self.paused, self.setting);
-                }
-                SpeedSetting::Fastest => {}
+            if self.paused && self.setting == SpeedSetting::Realtime {
+                self.paused = false;
+                self.unmeasured = (Duration::ZERO, Duration::ZERO);
+                self.rebuild_panel(ctx, app);
+            } else if let Some(setting) = self.setting.faster(self.speed_cap) {
+                self.setting = setting;
+                self.rebuild_panel(ctx, app);
             }
         }
 
         if !self.paused {
             if let Some(real_dt) = ctx.input.nonblocking_is_update_event() {
                 ctx.input.use_update_event();
-                let multiplier = match self.setting {
-                    SpeedSetting::Realtime => 1.0,
-                    SpeedSetting::Fast => 5.0,
-                    SpeedSetting::Faster => 30.0,
-                    SpeedSetting::Fastest => 3600.0,
-                };
-                let dt = multiplier * real_dt;
+                let dt = self.setting.multiplier() * real_dt;
+                let before = app.primary.sim.time();
                 // TODO This should match the update frequency in ezgui. Plumb along the deadline
                 // or frequency to here.
                 app.primary.sim.time_limited_step(
@@ -269,8 +516,26 @@ impl SpeedControls {
                     &mut app.primary.sim_cb,
                 );
                 app.recalculate_current_selection(ctx);
+                self.measure(ctx, app, real_dt, app.primary.sim.time() - before);
+
+                if let Some(cond) = self.pause_condition.take() {
+                    if (cond.check)(&app.primary.sim) {
+                        self.paused = true;
+                        self.rebuild_panel(ctx, app);
+                        return Some(Transition::Push(msg(
+                            "Paused",
+                            vec![format!(
+                                "At {}: {}",
+                                app.primary.sim.time(),
+                                cond.reason.describe()
+                            )],
+                        )));
+                    }
+                    self.pause_condition = Some(cond);
+                }
             }
         }
+        self.maybe_snapshot(app);
 
         // TODO Need to do this anywhere that steps the sim, like TimeWarpScreen.
         let alerts = app.primary.sim.clear_alerts();
@@ -318,21 +583,225 @@ impl SpeedControls {
     pub fn pause(&mut self, ctx: &mut EventCtx, app: &App) {
         if !self.paused {
             self.paused = true;
-            self.composite = SpeedControls::make_panel(ctx, app, self.paused, self.setting);
+            self.rebuild_panel(ctx, app);
         }
     }
 
     pub fn resume_realtime(&mut self, ctx: &mut EventCtx, app: &App) {
         if self.paused || self.setting != SpeedSetting::Realtime {
+            if self.paused {
+                self.unmeasured = (Duration::ZERO, Duration::ZERO);
+            }
             self.paused = false;
             self.setting = SpeedSetting::Realtime;
-            self.composite = SpeedControls::make_panel(ctx, app, self.paused, self.setting);
+            self.rebuild_panel(ctx, app);
         }
     }
 
     pub fn is_paused(&self) -> bool {
         self.paused
     }
+
+    pub fn set_pause_condition(&mut self, ctx: &mut EventCtx, app: &App, cond: PauseCondition) {
+        self.pause_condition = Some(cond);
+        self.rebuild_panel(ctx, app);
+    }
+
+    fn pausing_on_gridlock(&self) -> bool {
+        self.pause_condition
+            .as_ref()
+            .map(|c| match c.reason {
+                PauseReason::Gridlock => true,
+                PauseReason::Other(_) => false,
+            })
+            .unwrap_or(false)
+    }
+
+    fn measure(&mut self, ctx: &mut EventCtx, app: &App, real_dt: Duration, sim_dt: Duration) {
+        self.unmeasured.0 += real_dt;
+        self.unmeasured.1 += sim_dt;
+        if self.unmeasured.0 < MEASUREMENT_FREQUENCY {
+            return;
+        }
+        let (real, sim) = self.unmeasured;
+        self.unmeasured = (Duration::ZERO, Duration::ZERO);
+        self.measurements
+            .push_back((sim / real) / self.setting.multiplier());
+        if self.measurements.len() > MAX_MEASUREMENTS {
+            self.measurements.pop_front();
+        }
+
+        // A sparkline of the fraction achieved, with the latest actual speed
+        let width = 3.0;
+        let height = 20.0;
+        let mut batch = GeomBatch::new();
+        batch.push(
+            app.cs.section_bg,
+            Polygon::rectangle(width * (MAX_MEASUREMENTS as f64), height),
+        );
+        for (idx, pct) in self.measurements.iter().enumerate() {
+            let h = (height * pct.min(1.0)).max(1.0);
+            batch.push(
+                if *pct < KEEPING_UP {
+                    Color::RED
+                } else {
+                    Color::GREEN
+                },
+                Polygon::rectangle(width, h).translate(width * (idx as f64), height - h),
+            );
+        }
+        // Don't complain about one slow second
+        let falling_behind = self.measurements.len() >= 5
+            && self
+                .measurements
+                .iter()
+                .rev()
+                .take(5)
+                .all(|pct| *pct < KEEPING_UP);
+        let actual = self.measurements.back().unwrap() * self.setting.multiplier();
+        let mut line = Line(format!("{:.2}x", actual)).small();
+        if falling_behind {
+            line = line.fg(Color::RED);
+        }
+        self.composite.replace(
+            ctx,
+            "measured speed",
+            Widget::row(vec![
+                Widget::draw_batch(ctx, batch).margin_right(5),
+                Text::from(line).draw(ctx),
+            ])
+            .centered()
+            .margin_right(16)
+            .named("measured speed"),
+        );
+    }
+
+    fn maybe_snapshot(&mut self, app: &App) {
+        // After resuming from edits, old snapshots don't match the map anymore.
+        if !app.opts.dev || app.primary.dirty_from_edits {
+            self.snapshots.clear();
+            return;
+        }
+        let now = app.primary.sim.time();
+        if self
+            .last_snapshot
+            .map(|t| Duration::realtime_elapsed(t) < MIN_REAL_TIME_BETWEEN_SNAPSHOTS)
+            .unwrap_or(false)
+        {
+            return;
+        }
+        if self
+            .snapshots
+            .back()
+            .map(|sim| now - sim.time() >= SNAPSHOT_FREQUENCY)
+            .unwrap_or(true)
+        {
+            self.snapshots.push_back(app.primary.sim.clone());
+            self.last_snapshot = Some(Instant::now());
+            if self.snapshots.len() > MAX_SNAPSHOTS {
+                self.snapshots.pop_front();
+            }
+        }
+    }
+
+    fn set_custom_speed(&mut self, ctx: &mut EventCtx, app: &App, multiplier: f64) {
+        self.setting = SpeedSetting::Custom(multiplier);
+        self.rebuild_panel(ctx, app);
+    }
+
+    // Keeps the current speed if it's still allowed, otherwise runs as fast as the new cap allows.
+    pub fn set_speed_cap(&mut self, ctx: &mut EventCtx, app: &App, cap: f64) {
+        assert!(cap > 0.0);
+        self.speed_cap = cap;
+        self.setting = self.setting.clamp(cap);
+        self.rebuild_panel(ctx, app);
+    }
+
+    // Like jumping back in time, recreate SandboxMode so nothing in the UI refers to the future, then
+    // swap in an earlier sim. The snapshots carry over.
+    fn rewind_to(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &mut App,
+        maybe_mode: Option<&GameplayMode>,
+        sim: Sim,
+    ) -> Transition {
+        if let Some(mode) = maybe_mode {
+            let mut sandbox = SandboxMode::new(ctx, app, mode.clone());
+            app.primary.sim = sim;
+            app.recalculate_current_selection(ctx);
+            if let Some(ref mut speed) = sandbox.controls.speed {
+                speed.restore(ctx, app, (self.paused, self.setting));
+                speed.snapshots = std::mem::take(&mut self.snapshots);
+            }
+            Transition::Replace(Box::new(sandbox))
+        } else {
+            Transition::Push(msg(
+                "Error",
+                vec!["Sorry, you can't go rewind time from this mode."],
+            ))
+        }
+    }
+
+    // When SandboxMode gets recreated to rewind time, carry over the old speed.
+    fn restore(&mut self, ctx: &mut EventCtx, app: &App, (paused, setting): (bool, SpeedSetting)) {
+        if self.paused != paused || self.setting != setting {
+            self.paused = paused;
+            self.setting = setting.clamp(self.speed_cap);
+            self.rebuild_panel(ctx, app);
+        }
+    }
+}
+
+fn choose_custom_speed(
+    wiz: &mut Wizard,
+    ctx: &mut EventCtx,
+    _: &mut App,
+    max: f64,
+) -> Option<Transition> {
+    let multiplier = wiz.wrap(ctx).input_something(
+        &format!("Run how many times faster than real-time? (up to {})", max),
+        None,
+        Box::new(move |line| line.parse::<f64>().ok().filter(|x| *x > 0.0 && *x <= max)),
+    )?;
+    Some(Transition::PopWithData(Box::new(move |state, ctx, app| {
+        if let Some(sandbox) = state.downcast_mut::<SandboxMode>() {
+            if let Some(ref mut speed) = sandbox.controls.speed {
+                speed.set_custom_speed(ctx, app, multiplier);
+            }
+        }
+    })))
+}
+
+fn choose_speed_cap(wiz: &mut Wizard, ctx: &mut EventCtx, _: &mut App) -> Option<Transition> {
+    let cap = wiz.wrap(ctx).input_something(
+        "What's the fastest the simulation should run, in times faster than real-time?",
+        None,
+        Box::new(|line| line.parse::<f64>().ok().filter(|x| *x > 0.0)),
+    )?;
+    Some(Transition::PopWithData(Box::new(move |state, ctx, app| {
+        if let Some(sandbox) = state.downcast_mut::<SandboxMode>() {
+            if let Some(ref mut speed) = sandbox.controls.speed {
+                speed.set_speed_cap(ctx, app, cap);
+            }
+        }
+    })))
+}
+
+// The usual increments, plus a custom one (like from --time_increment) if necessary. Largest
+// first.
+fn step_choices(current: Duration) -> Vec<Choice<Duration>> {
+    let mut choices = vec![
+        Choice::new("+1h", Duration::hours(1)),
+        Choice::new("+30m", Duration::minutes(30)),
+        Choice::new("+10m", Duration::minutes(10)),
+        Choice::new("+0.1s", TINY_STEP),
+    ];
+    if !choices.iter().any(|c| c.data == current) {
+        choices.push(Choice::new(format!("+{}", current), current));
+        choices.sort_by(|a, b| b.data.partial_cmp(&a.data).unwrap());
+    }
+    choices
 }
 
 // TODO Text entry would be great
@@ -340,15 +809,23 @@ struct JumpToTime {
     composite: Composite,
     target: Time,
     maybe_mode: Option<GameplayMode>,
+    // (paused, setting) of the SpeedControls that opened this
+    speed: (bool, SpeedSetting),
 }
 
 impl JumpToTime {
-    fn new(ctx: &mut EventCtx, app: &App, maybe_mode: Option<GameplayMode>) -> JumpToTime {
+    fn new(
+        ctx: &mut EventCtx,
+        app: &App,
+        maybe_mode: Option<GameplayMode>,
+        speed: (bool, SpeedSetting),
+    ) -> JumpToTime {
         let target = app.primary.sim.time();
         let end_of_day = app.primary.sim.get_end_of_day();
         JumpToTime {
             target,
             maybe_mode,
+            speed,
             composite: Composite::new(
                 Widget::col(vec![
                     Widget::row(vec![
@@ -417,8 +894,12 @@ impl State for JumpToTime {
                 "jump to time" => {
                     if self.target < app.primary.sim.time() {
                         if let Some(mode) = self.maybe_mode.take() {
+                            let mut sandbox = SandboxMode::new(ctx, app, mode);
+                            if let Some(ref mut speed) = sandbox.controls.speed {
+                                speed.restore(ctx, app, self.speed);
+                            }
                             return Transition::ReplaceThenPush(
-                                Box::new(SandboxMode::new(ctx, app, mode)),
+                                Box::new(sandbox),
                                 TimeWarpScreen::new(ctx, app, self.target, false),
                             );
                         } else {
@@ -475,10 +956,18 @@ impl State for JumpToTime {
 pub struct TimeWarpScreen {
     target: Time,
     started: Instant,
+    // The sim time when the warp started, to measure throughput
+    started_sim_time: Time,
+    // If the sim time hasn't changed in a while, give up instead of looking frozen
+    last_sim_time: Time,
+    stalled_frames: usize,
     traffic_jams: bool,
     composite: Composite,
 }
 
+// How many update events in a row can pass without the sim time advancing
+const MAX_STALLED_FRAMES: usize = 30;
+
 impl TimeWarpScreen {
     pub fn new(
         ctx: &mut EventCtx,
@@ -503,6 +992,9 @@ impl TimeWarpScreen {
         Box::new(TimeWarpScreen {
             target,
             started: Instant::now(),
+            started_sim_time: app.primary.sim.time(),
+            last_sim_time: app.primary.sim.time(),
+            stalled_frames: 0,
             traffic_jams,
             composite: Composite::new(
                 Widget::col(vec![
@@ -519,6 +1011,23 @@ impl TimeWarpScreen {
     }
 }
 
+impl TimeWarpScreen {
+    // Assumes the rest of the warp runs at the average speed so far. None until there's enough
+    // to measure.
+    fn estimate_remaining(&self, now: Time) -> Option<Duration> {
+        let real_elapsed = Duration::realtime_elapsed(self.started);
+        let sim_elapsed = now - self.started_sim_time;
+        if real_elapsed < Duration::seconds(1.0) || sim_elapsed == Duration::ZERO {
+            return None;
+        }
+        // Sim seconds per real second
+        let throughput = sim_elapsed / real_elapsed;
+        Some(Duration::seconds(
+            (self.target - now).inner_seconds() / throughput,
+        ))
+    }
+}
+
 impl State for TimeWarpScreen {
     fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
         if ctx.input.nonblocking_is_update_event().is_some() {
@@ -555,6 +1064,22 @@ impl State for TimeWarpScreen {
             }
 
             let now = app.primary.sim.time();
+            if now == self.last_sim_time && now != self.target {
+                self.stalled_frames += 1;
+                if self.stalled_frames >= MAX_STALLED_FRAMES {
+                    return Transition::Replace(msg(
+                        "Time warp stopped",
+                        vec![format!(
+                            "Simulation appears stuck at {}; stopping warp.",
+                            now.ampm_tostring()
+                        )],
+                    ));
+                }
+            } else {
+                self.last_sim_time = now;
+                self.stalled_frames = 0;
+            }
+
             let (finished_after, _, _) = app.primary.sim.num_trips();
             let finished_before = if app.has_prebaked().is_some() {
                 let mut cnt = 0;
@@ -580,6 +1105,12 @@ impl State for TimeWarpScreen {
                     "Elapsed simulation time: {}",
                     Duration::realtime_elapsed(self.started)
                 )),
+                Line(format!(
+                    "Estimated time remaining: {}",
+                    self.estimate_remaining(now)
+                        .map(|dt| dt.to_string())
+                        .unwrap_or_else(|| "...".to_string())
+                )),
                 if let Some(n) = finished_before {
                     Line(format!(
                         "Finished trips: {} ({} compared to before \"{}\")",
@@ -1,8 +1,11 @@
 use abstutil::{prettyprint_usize, CmdArgs, Timer};
+use geom::{Duration, Time};
 use map_model::Map;
 use rand::SeedableRng;
 use rand_xorshift::XorShiftRng;
-use sim::{AlertHandler, Scenario, Sim, SimFlags};
+use sim::{AlertHandler, MetricsRow, Scenario, Sim, SimFlags};
+use std::fs::File;
+use std::io::Write;
 
 // This is specialized to experiment with running the pandemic model over long time periods.
 // Original functionality for profiling and debugging gridlock have been removed.
@@ -12,6 +15,8 @@ fn main() {
     let num_days = args
         .optional_parse("--days", |s| s.parse::<usize>())
         .unwrap_or(1);
+    // If set, write Sim::snapshot_metrics every sim-minute to this CSV file
+    let metrics_path = args.optional("--metrics");
     args.done();
 
     let mut sim_flags = SimFlags::synthetic_test("montlake", "pandemic");
@@ -34,7 +39,32 @@ fn main() {
         .instantiate(&mut sim, &map, &mut rng, &mut timer);
     timer.done();
 
-    run_experiment(&map, &mut sim);
+    if let Some(path) = metrics_path {
+        record_metrics(&map, &mut sim, path, num_days);
+    } else {
+        run_experiment(&map, &mut sim);
+    }
+}
+
+// Stops when the sim is done or after num_days, whichever comes first. Gridlock could otherwise
+// keep this going forever.
+fn record_metrics(map: &Map, sim: &mut Sim, path: String, num_days: usize) {
+    let timer = Timer::new("run sim until done, recording metrics");
+    let end = Time::START_OF_DAY + Duration::hours(24 * num_days);
+    let mut f = File::create(&path).unwrap();
+    writeln!(f, "{}", MetricsRow::CSV_HEADER).unwrap();
+    writeln!(f, "{}", sim.snapshot_metrics().to_csv()).unwrap();
+    while !sim.is_done() && sim.time() < end {
+        sim.timed_step(
+            map,
+            Duration::minutes(1),
+            &mut None,
+            &mut Timer::throwaway(),
+        );
+        writeln!(f, "{}", sim.snapshot_metrics().to_csv()).unwrap();
+    }
+    timer.done();
+    println!("Done at {}. Metrics in {}", sim.time(), path);
 }
 
 fn run_experiment(map: &Map, sim: &mut Sim) {
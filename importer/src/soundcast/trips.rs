@@ -1,10 +1,10 @@
-use crate::soundcast::popdat::{Endpoint, OrigTrip, PopDat};
+use crate::soundcast::popdat::{Endpoint, OrigTrip, PopDat, Purpose};
 use abstutil::{prettyprint_usize, MultiMap, Timer};
 use geom::LonLat;
 use map_model::{BuildingID, IntersectionID, Map, PathConstraints, PathRequest, PathStep};
 use sim::{
-    IndividTrip, OffMapLocation, OrigPersonID, PersonID, PersonSpec, Scenario, SpawnTrip,
-    TripEndpoint, TripMode,
+    IndividTrip, OffMapLocation, OrigPersonID, PersonID, PersonSpec, Scenario, SeedParkedCars,
+    SpawnTrip, TripEndpoint, TripMode, TripPurpose,
 };
 use std::collections::HashMap;
 
@@ -247,18 +247,23 @@ pub fn make_weekday_scenario(
     // person -> (trip seq, index into individ_trips)
     let mut trips_per_person: MultiMap<OrigPersonID, ((usize, bool, usize), usize)> =
         MultiMap::new();
-    for (trip, depart, person, seq) in
+    for (trip, depart, person, seq, purpose) in
         timer.parallelize("turn Soundcast trips into SpawnTrips", trips, |trip| {
             (
                 SpawnTrip::new(trip.from, trip.to, trip.orig.mode, map),
                 trip.orig.depart_at,
                 trip.orig.person,
                 trip.orig.seq,
+                trip_purpose(trip.orig.purpose.1),
             )
         })
     {
         let idx = individ_trips.len();
-        individ_trips.push(Some(IndividTrip { depart, trip }));
+        individ_trips.push(Some(IndividTrip {
+            depart,
+            trip,
+            purpose,
+        }));
         trips_per_person.insert(person, (seq, idx));
     }
     timer.note(format!(
@@ -282,6 +287,8 @@ pub fn make_weekday_scenario(
         people.push(PersonSpec {
             id,
             orig_id: Some(orig_id),
+            provenance: None,
+            label: None,
             trips,
         });
     }
@@ -296,6 +303,9 @@ pub fn make_weekday_scenario(
         map_name: map.get_name().to_string(),
         people,
         only_seed_buses: None,
+        seed_parked_cars: SeedParkedCars::default(),
+        events: Vec::new(),
+        warmup: None,
     }
     .remove_weird_schedules(map)
 }
@@ -328,6 +338,7 @@ pub fn make_weekday_scenario_with_everyone(
         individ_trips.push(Some(IndividTrip {
             depart: orig_trip.depart_at,
             trip,
+            purpose: trip_purpose(orig_trip.purpose.1),
         }));
         trips_per_person.insert(orig_trip.person, (orig_trip.seq, idx));
     }
@@ -352,6 +363,8 @@ pub fn make_weekday_scenario_with_everyone(
         people.push(PersonSpec {
             id,
             orig_id: Some(orig_id),
+            provenance: None,
+            label: None,
             trips,
         });
     }
@@ -366,6 +379,27 @@ pub fn make_weekday_scenario_with_everyone(
         map_name: map.get_name().to_string(),
         people,
         only_seed_buses: None,
+        seed_parked_cars: SeedParkedCars::default(),
+        events: Vec::new(),
+        warmup: None,
     }
     .remove_weird_schedules(map)
 }
+
+// Soundcast records why someone's going to their destination
+fn trip_purpose(p: Purpose) -> TripPurpose {
+    match p {
+        Purpose::Home => TripPurpose::Home,
+        Purpose::Work => TripPurpose::Work,
+        Purpose::School => TripPurpose::School,
+        Purpose::Escort => TripPurpose::Escort,
+        Purpose::PersonalBusiness => TripPurpose::PersonalBusiness,
+        Purpose::Shopping => TripPurpose::Shopping,
+        Purpose::Meal => TripPurpose::Meal,
+        Purpose::Social => TripPurpose::Social,
+        Purpose::Recreation => TripPurpose::Recreation,
+        Purpose::Medical => TripPurpose::Medical,
+        // Just a transfer along the way; the real purpose is on another leg
+        Purpose::ParkAndRideTransfer => TripPurpose::Unknown,
+    }
+}
@@ -213,7 +213,8 @@ impl Model {
                     ]);
                 }
 
-                // (MAX_CAR_LENGTH + sim::FOLLOWING_DISTANCE) from sim, but without the dependency
+                // MAX_CAR_LENGTH plus the default DrivingConfig::following_distance from sim, but
+                // without the dependency
                 txt.add(Line(format!(
                     "Can fit ~{} cars",
                     (PolyLine::new(road.center_points.clone()).length()
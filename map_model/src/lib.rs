@@ -32,7 +32,7 @@ pub use crate::map::Map;
 pub use crate::parking_lot::{ParkingLot, ParkingLotID};
 pub use crate::pathfind::uber_turns::{IntersectionCluster, UberTurn, UberTurnGroup};
 pub use crate::pathfind::{Path, PathConstraints, PathRequest, PathStep};
-pub use crate::road::{DirectedRoadID, Road, RoadID};
+pub use crate::road::{DirectedRoadID, OriginalDirectedRoad, Road, RoadID};
 pub use crate::stop_signs::{ControlStopSign, RoadWithStopSign};
 pub use crate::traffic_signals::{ControlTrafficSignal, Phase};
 pub use crate::traversable::{Position, Traversable};
@@ -82,6 +82,33 @@ impl DirectedRoadID {
             constraints.filter_lanes(&r.children_backwards.iter().map(|(l, _)| *l).collect(), map)
         }
     }
+
+    pub fn to_permanent(self, map: &Map) -> OriginalDirectedRoad {
+        OriginalDirectedRoad {
+            parent: map.get_r(self.id).orig_id,
+            forwards: self.forwards,
+        }
+    }
+}
+
+// Like DirectedRoadID, but still valid after the map is re-imported
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct OriginalDirectedRoad {
+    pub parent: OriginalRoad,
+    pub forwards: bool,
+}
+
+impl OriginalDirectedRoad {
+    pub fn from_permanent(self, map: &Map) -> Result<DirectedRoadID, String> {
+        let r = map.find_r_by_osm_id(
+            self.parent.osm_way_id,
+            (self.parent.i1.osm_node_id, self.parent.i2.osm_node_id),
+        )?;
+        Ok(DirectedRoadID {
+            id: r,
+            forwards: self.forwards,
+        })
+    }
 }
 
 // These're bidirectional (possibly)
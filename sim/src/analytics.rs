@@ -1,4 +1,6 @@
-use crate::{AlertLocation, CarID, Event, ParkingSpot, TripID, TripMode, TripPhaseType};
+use crate::{
+    AlertLocation, CarID, Event, ParkingSpot, TripID, TripMode, TripPhaseType, TripPurpose,
+};
 use abstutil::Counter;
 use geom::{Distance, Duration, Histogram, Time};
 use map_model::{
@@ -21,6 +23,12 @@ pub struct Analytics {
     // TODO Hack: No TripMode means aborted
     // Finish time, ID, mode (or None as aborted), trip duration
     pub finished_trips: Vec<(Time, TripID, Option<TripMode>, Duration)>,
+    // How many trips finished, grouped by their purpose
+    #[serde(default)]
+    pub finished_trips_per_purpose: BTreeMap<TripPurpose, usize>,
+    // Trips that never started on the map, like metered spawns that gave up waiting
+    #[serde(default)]
+    pub cancelled_trips: Vec<(Time, TripID)>,
     // TODO This subsumes finished_trips
     pub trip_log: Vec<(Time, TripID, Option<PathRequest>, TripPhaseType)>,
     pub intersection_delays: BTreeMap<IntersectionID, Vec<(Time, Duration, TripMode)>>,
@@ -35,6 +43,20 @@ pub struct Analytics {
     record_anything: bool,
 }
 
+// A summary of how trips went, for comparing runs without the GUI
+#[derive(Clone, Debug, Default)]
+pub struct TripStats {
+    pub completed: usize,
+    pub aborted: usize,
+    pub cancelled: usize,
+    // Trips that hadn't finished when the run stopped, including ones that never started
+    pub in_progress: usize,
+    // Only modes with at least one completed trip are present
+    pub mean_duration: BTreeMap<TripMode, Duration>,
+    pub median_duration: BTreeMap<TripMode, Duration>,
+    pub completed_per_purpose: BTreeMap<TripPurpose, usize>,
+}
+
 impl Analytics {
     pub fn new() -> Analytics {
         Analytics {
@@ -45,6 +67,8 @@ impl Analytics {
             bus_passengers_waiting: Vec::new(),
             started_trips: BTreeMap::new(),
             finished_trips: Vec::new(),
+            finished_trips_per_purpose: BTreeMap::new(),
+            cancelled_trips: Vec::new(),
             trip_log: Vec::new(),
             intersection_delays: BTreeMap::new(),
             parking_lane_changes: BTreeMap::new(),
@@ -103,15 +127,19 @@ impl Analytics {
         if let Event::TripFinished {
             trip,
             mode,
+            purpose,
             total_time,
             ..
         } = ev
         {
             self.finished_trips
                 .push((time, trip, Some(mode), total_time));
+            *self.finished_trips_per_purpose.entry(purpose).or_insert(0) += 1;
         } else if let Event::TripAborted(id) = ev {
             self.started_trips.entry(id).or_insert(time);
             self.finished_trips.push((time, id, None, Duration::ZERO));
+        } else if let Event::TripCancelled(id) = ev {
+            self.cancelled_trips.push((time, id));
         }
 
         // Intersection delays
@@ -521,6 +549,33 @@ impl Analytics {
         }
         pts
     }
+
+    pub fn trip_stats(&self, in_progress: usize) -> TripStats {
+        let mut stats = TripStats {
+            in_progress,
+            cancelled: self.cancelled_trips.len(),
+            completed_per_purpose: self.finished_trips_per_purpose.clone(),
+            ..Default::default()
+        };
+        let mut per_mode: BTreeMap<TripMode, Vec<Duration>> = BTreeMap::new();
+        for (_, _, maybe_mode, dt) in &self.finished_trips {
+            if let Some(mode) = maybe_mode {
+                stats.completed += 1;
+                per_mode.entry(*mode).or_insert_with(Vec::new).push(*dt);
+            } else {
+                stats.aborted += 1;
+            }
+        }
+        for (mode, mut times) in per_mode {
+            times.sort();
+            let total: Duration = times.iter().fold(Duration::ZERO, |sum, dt| sum + *dt);
+            stats
+                .mean_duration
+                .insert(mode, total / (times.len() as f64));
+            stats.median_duration.insert(mode, times[times.len() / 2]);
+        }
+        stats
+    }
 }
 
 impl Default for Analytics {
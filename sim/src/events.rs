@@ -1,7 +1,8 @@
 use crate::{
     AgentID, CarID, OffMapLocation, ParkingSpot, PedestrianID, PersonID, TripID, TripMode,
+    TripPurpose,
 };
-use geom::Duration;
+use geom::{Duration, Time};
 use map_model::{
     BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, Map, Path, PathRequest, Traversable,
 };
@@ -35,10 +36,13 @@ pub enum Event {
     TripFinished {
         trip: TripID,
         mode: TripMode,
+        purpose: TripPurpose,
         total_time: Duration,
         blocked_time: Duration,
     },
     TripAborted(TripID),
+    // A metered spawn that gave up waiting to enter the map
+    TripCancelled(TripID),
     TripPhaseStarting(TripID, PersonID, Option<PathRequest>, TripPhaseType),
 
     // Just use for parking replanning. Not happy about copying the full path in here, but the way
@@ -48,6 +52,84 @@ pub enum Event {
     Alert(AlertLocation, String),
 }
 
+// A simpler record of what happened to someone, for logging and analyzing offline. Unlike Event,
+// each one is stamped with the time.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum SimEvent {
+    // Includes spawning, starting to drive or walk, parking, and waiting for a bus
+    TripPhaseStarted {
+        time: Time,
+        trip: TripID,
+        person: PersonID,
+        phase: TripPhaseType,
+    },
+    CarParked {
+        time: Time,
+        car: CarID,
+        spot: ParkingSpot,
+    },
+    CarUnparked {
+        time: Time,
+        car: CarID,
+        spot: ParkingSpot,
+    },
+    TripFinished {
+        time: Time,
+        trip: TripID,
+        mode: TripMode,
+        purpose: TripPurpose,
+        total_time: Duration,
+    },
+    TripAborted {
+        time: Time,
+        trip: TripID,
+    },
+    TripCancelled {
+        time: Time,
+        trip: TripID,
+    },
+}
+
+impl SimEvent {
+    // Most events aren't interesting enough to log
+    pub(crate) fn from_event(time: Time, ev: &Event) -> Option<SimEvent> {
+        match ev {
+            Event::TripPhaseStarting(trip, person, _, phase) => Some(SimEvent::TripPhaseStarted {
+                time,
+                trip: *trip,
+                person: *person,
+                phase: *phase,
+            }),
+            Event::CarReachedParkingSpot(car, spot) => Some(SimEvent::CarParked {
+                time,
+                car: *car,
+                spot: *spot,
+            }),
+            Event::CarLeftParkingSpot(car, spot) => Some(SimEvent::CarUnparked {
+                time,
+                car: *car,
+                spot: *spot,
+            }),
+            Event::TripFinished {
+                trip,
+                mode,
+                purpose,
+                total_time,
+                ..
+            } => Some(SimEvent::TripFinished {
+                time,
+                trip: *trip,
+                mode: *mode,
+                purpose: *purpose,
+                total_time: *total_time,
+            }),
+            Event::TripAborted(trip) => Some(SimEvent::TripAborted { time, trip: *trip }),
+            Event::TripCancelled(trip) => Some(SimEvent::TripCancelled { time, trip: *trip }),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum AlertLocation {
     Nil,
@@ -10,23 +10,30 @@ mod sim;
 mod transit;
 mod trips;
 
-pub use self::analytics::{Analytics, TripPhase};
+pub use self::analytics::{Analytics, TripPhase, TripStats};
 pub(crate) use self::events::Event;
-pub use self::events::{AlertLocation, TripPhaseType};
+pub use self::events::{AlertLocation, SimEvent, TripPhaseType};
 pub use self::make::{
-    BorderSpawnOverTime, IndividTrip, OffMapLocation, OriginDestination, PersonSpec, Scenario,
-    ScenarioGenerator, SimFlags, SpawnOverTime, SpawnTrip, TripSpawner, TripSpec,
+    BorderSpawnOverTime, FeasibilityReport, IndividTrip, InstantiateSummary, OffMapLocation,
+    OriginDestination, OriginWeighting, ParkedCarCount, ParkedVehicleSpec, PersonSpec, Scenario,
+    ScenarioBlock, ScenarioGenerator, SeedParkedCars, SimFlags, SpawnOverTime, SpawnTrip,
+    TimeDistribution, TimedEvent, TripSpawner, TripSpec,
 };
+pub use self::mechanics::DrivingConfig;
 pub(crate) use self::mechanics::{
     DrivingSimState, IntersectionSimState, ParkingSimState, WalkingSimState,
 };
 pub(crate) use self::pandemic::PandemicModel;
 pub(crate) use self::router::{ActionAtEnd, Router};
 pub(crate) use self::scheduler::{Command, Scheduler};
-pub use self::sim::{AgentProperties, AlertHandler, Sim, SimCallback, SimOptions};
+pub use self::sim::{
+    run_scenario_headless, AgentDescription, AgentProperties, AlertHandler, MetricsRow, Sim,
+    SimCallback, SimOptions, SteadyStateThresholds,
+};
+pub use self::transit::TransitRiderState;
 pub(crate) use self::transit::TransitSimState;
 pub use self::trips::{Person, PersonState, TripResult};
-pub use self::trips::{TripEndpoint, TripMode};
+pub use self::trips::{TripEndpoint, TripMode, TripPurpose};
 pub(crate) use self::trips::{TripLeg, TripManager};
 pub use crate::render::{
     CarStatus, DontDrawAgents, DrawCarInput, DrawPedCrowdInput, DrawPedestrianInput, GetDrawAgents,
@@ -50,10 +57,6 @@ pub const MAX_CAR_LENGTH: Distance = Distance::const_meters(6.5);
 // Note this is more than MAX_CAR_LENGTH
 pub const BUS_LENGTH: Distance = Distance::const_meters(12.5);
 
-// At all speeds (including at rest), cars must be at least this far apart, measured from front of
-// one car to the back of the other.
-pub const FOLLOWING_DISTANCE: Distance = Distance::const_meters(1.0);
-
 // The numeric ID must be globally unique, without considering VehicleType. VehicleType is bundled
 // for convenient debugging.
 // TODO Implement Eq, Hash, Ord manually to guarantee this.
@@ -497,6 +500,8 @@ pub struct CreateCar {
     pub maybe_parked_car: Option<ParkedCar>,
     // None for buses
     pub trip_and_person: Option<(TripID, PersonID)>,
+    // For metered spawns, wait for the start of the lane to be clear, and give up after this
+    pub give_up_at: Option<Time>,
 }
 
 impl CreateCar {
@@ -515,6 +520,7 @@ impl CreateCar {
             start_dist: start_pos.dist_along(),
             maybe_parked_car: None,
             trip_and_person: Some((trip, person)),
+            give_up_at: None,
         }
     }
 
@@ -534,6 +540,7 @@ impl CreateCar {
             start_dist,
             maybe_parked_car: Some(parked_car),
             trip_and_person: Some((trip, person)),
+            give_up_at: None,
         }
     }
 }
@@ -1,9 +1,15 @@
-use crate::{DrivingGoal, IndividTrip, PersonID, PersonSpec, Scenario, SidewalkSpot, SpawnTrip};
+use crate::{
+    DrivingGoal, IndividTrip, PersonID, PersonSpec, Scenario, SidewalkSpot, SpawnTrip, TripPurpose,
+};
 use abstutil::Timer;
 use geom::{Duration, Time};
-use map_model::{BuildingID, DirectedRoadID, Map, PathConstraints};
+use map_model::{
+    BuildingID, BusStopID, DirectedRoadID, IntersectionID, LaneID, Map, OriginalDirectedRoad,
+    PathConstraints, Position,
+};
 use rand::seq::SliceRandom;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Distribution, Normal};
 use rand_xorshift::XorShiftRng;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
@@ -24,10 +30,38 @@ pub struct ScenarioGenerator {
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct SpawnOverTime {
     pub num_agents: usize,
-    // TODO use https://docs.rs/rand/0.5.5/rand/distributions/struct.Normal.html
+    // The chance of one more agent, so the expected number can be fractional, like 2.5. Usually
+    // set through set_expected_agents, which keeps it in [0, 1). Other values are folded into
+    // num_agents, so 1.5 means one more agent and a 50% chance of another.
+    #[serde(default)]
+    pub extra_agent_chance: f64,
     pub start_time: Time,
     pub stop_time: Time,
+    #[serde(default)]
+    pub distribution: TimeDistribution,
+    // If set, nudge each departure time later by up to this much, so spawns don't line up on the
+    // same instants. Keep it small -- a second or less.
+    #[serde(default)]
+    pub jitter: Option<Duration>,
+    // If empty, agents start from any building. Otherwise, pick from these buildings,
+    // proportional to the weights.
+    #[serde(default)]
+    pub origin_weights: Vec<(BuildingID, f64)>,
+    // How to pick buildings when origin_weights is empty, and for a goal of Anywhere
+    #[serde(default)]
+    pub origin_weighting: OriginWeighting,
     pub goal: OriginDestination,
+    // How many times to re-pick a goal that's the same as the origin. After that, the goal is
+    // used anyway, so tiny maps still get trips.
+    #[serde(default)]
+    pub goal_retries: usize,
+    // If the goal is a border that cars or bikes can't use, send them to the closest border that
+    // works instead of dropping them.
+    #[serde(default)]
+    pub nearest_border_fallback: bool,
+    // Tags every trip, for breaking down results later
+    #[serde(default)]
+    pub purpose: TripPurpose,
     pub percent_driving: f64,
     pub percent_biking: f64,
     pub percent_use_transit: f64,
@@ -39,49 +73,229 @@ pub struct BorderSpawnOverTime {
     pub num_cars: usize,
     pub num_bikes: usize,
     pub percent_use_transit: f64,
-    // TODO use https://docs.rs/rand/0.5.5/rand/distributions/struct.Normal.html
     pub start_time: Time,
     pub stop_time: Time,
+    #[serde(default)]
+    pub distribution: TimeDistribution,
     pub start_from_border: DirectedRoadID,
+    // If this isn't empty, each agent starts from a random one of these instead of
+    // start_from_border.
+    #[serde(default)]
+    pub start_from_borders: Vec<DirectedRoadID>,
+    // If this isn't empty, these are used instead of start_from_border(s). Unlike the raw IDs,
+    // they still point at the right roads after the map is re-imported.
+    #[serde(default)]
+    pub permanent_borders: Vec<OriginalDirectedRoad>,
+    // If set, cars and bikes are spawned in groups of this size, like a convoy. Everyone in a
+    // group leaves at the same time from the same border to the same goal, and they enter the map
+    // back-to-back as soon as there's room.
+    #[serde(default)]
+    pub platoon_size: Option<usize>,
+    // If true, cars and bikes only enter once the start of the border lane is clear, instead of
+    // piling up the moment there's any room. Anybody still waiting when stop_time passes gives up.
+    #[serde(default)]
+    pub metering: bool,
+    // Same as in SpawnOverTime
+    #[serde(default)]
+    pub nearest_border_fallback: bool,
+    #[serde(default)]
+    pub purpose: TripPurpose,
     pub goal: OriginDestination,
 }
 
+// How departure times are picked. Samples always land in [start_time, stop_time].
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum TimeDistribution {
+    Uniform,
+    // Samples outside the time window are thrown out and drawn again, so the window cuts off the
+    // bell curve. If the window is so far out in a tail that that keeps failing, the sample is
+    // clamped to the window instead.
+    Normal { mean: Time, std_dev: Duration },
+}
+
+impl Default for TimeDistribution {
+    fn default() -> TimeDistribution {
+        TimeDistribution::Uniform
+    }
+}
+
+impl TimeDistribution {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            TimeDistribution::Uniform => Ok(()),
+            TimeDistribution::Normal { mean, std_dev } => {
+                if !mean.inner_seconds().is_finite() {
+                    return Err(format!("the mean {} isn't finite", mean.inner_seconds()));
+                }
+                if !std_dev.inner_seconds().is_finite() || *std_dev < Duration::ZERO {
+                    return Err(format!("the std_dev {} is bad", std_dev.inner_seconds()));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// How buildings are picked when any one will do
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum OriginWeighting {
+    Uniform,
+    // Bigger buildings, by area, are picked more often. An office tower produces more trips than
+    // a house.
+    BySize,
+}
+
+impl Default for OriginWeighting {
+    fn default() -> OriginWeighting {
+        OriginWeighting::Uniform
+    }
+}
+
+impl OriginWeighting {
+    fn pick_bldg(&self, map: &Map, rng: &mut XorShiftRng) -> BuildingID {
+        match self {
+            OriginWeighting::Uniform => map.all_buildings().choose(rng).unwrap().id,
+            // If every building somehow has no area, any one will do
+            OriginWeighting::BySize => match map
+                .all_buildings()
+                .choose_weighted(rng, |b| b.polygon.area())
+            {
+                Ok(b) => b.id,
+                Err(_) => map.all_buildings().choose(rng).unwrap().id,
+            },
+        }
+    }
+}
+
+// One SpawnOverTime or BorderSpawnOverTime in a ScenarioGenerator, by index
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ScenarioBlock {
+    SpawnOverTime(usize),
+    BorderSpawnOverTime(usize),
+}
+
+impl ScenarioBlock {
+    // Generated people remember their block through PersonSpec::provenance, so instantiating can
+    // key its random streams by block too.
+    pub fn provenance(self) -> String {
+        match self {
+            ScenarioBlock::SpawnOverTime(idx) => format!("spawn_over_time {}", idx),
+            ScenarioBlock::BorderSpawnOverTime(idx) => format!("border_spawn_over_time {}", idx),
+        }
+    }
+
+    pub fn from_provenance(provenance: &str) -> Option<ScenarioBlock> {
+        let mut parts = provenance.split(' ');
+        let kind = parts.next()?;
+        let idx = parts.next()?.parse::<usize>().ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+        match kind {
+            "spawn_over_time" => Some(ScenarioBlock::SpawnOverTime(idx)),
+            "border_spawn_over_time" => Some(ScenarioBlock::BorderSpawnOverTime(idx)),
+            _ => None,
+        }
+    }
+}
+
 impl ScenarioGenerator {
-    // TODO may need to fork the RNG a bit more
     pub fn generate(&self, map: &Map, rng: &mut XorShiftRng, timer: &mut Timer) -> Scenario {
+        let seed = rng.gen();
+        self.generate_blocks(&self.all_blocks(), seed, PersonID(0), map, timer)
+    }
+
+    pub fn all_blocks(&self) -> Vec<ScenarioBlock> {
+        let mut blocks: Vec<ScenarioBlock> = (0..self.spawn_over_time.len())
+            .map(ScenarioBlock::SpawnOverTime)
+            .collect();
+        blocks
+            .extend((0..self.border_spawn_over_time.len()).map(ScenarioBlock::BorderSpawnOverTime));
+        blocks
+    }
+
+    // Only generates the people from some blocks. Each block draws from its own RNG streams,
+    // keyed by the seed and the block, so its people don't depend on the other blocks at all.
+    // People are numbered from first_person, so the result can be instantiated onto a Sim that
+    // already has that many people. (The buses are only seeded when starting from nobody.)
+    //
+    // Scenario::instantiate keys its streams by block too. To iterate on one block, instantiate
+    // the rest once, keep a copy of that Sim, and instantiate just the edited block onto a fresh
+    // copy each time, with an RNG in the same state. Everybody gets the same vehicles, speeds, and
+    // trips as instantiating the whole scenario at once. The parked cars are the exception: they
+    // get spots across everybody being instantiated, so where they start differs.
+    pub fn generate_blocks(
+        &self,
+        blocks: &[ScenarioBlock],
+        seed: u64,
+        first_person: PersonID,
+        map: &Map,
+        timer: &mut Timer,
+    ) -> Scenario {
+        if let Err(problems) = self.validate(map) {
+            panic!(
+                "{} has {} problems:\n{}",
+                self.scenario_name,
+                problems.len(),
+                problems.join("\n")
+            );
+        }
         let mut scenario = Scenario::empty(map, &self.scenario_name);
-        scenario.only_seed_buses = self.only_seed_buses.clone();
+        scenario.only_seed_buses = if first_person == PersonID(0) {
+            self.only_seed_buses.clone()
+        } else {
+            Some(BTreeSet::new())
+        };
 
         timer.start(format!("Generating scenario {}", self.scenario_name));
 
-        for s in &self.spawn_over_time {
-            timer.start_iter("SpawnOverTime each agent", s.num_agents);
-            for _ in 0..s.num_agents {
-                timer.next();
-                s.spawn_agent(rng, &mut scenario, map, timer);
+        // People who might use transit start out walking. Figuring out if transit is worth it is
+        // expensive, so batch all of those queries up and do them in parallel at the end.
+        let mut transit_queries: Vec<TransitQuery> = Vec::new();
+
+        for block in blocks {
+            let mut streams = RngStreams::for_block(seed, Some(*block));
+            let start = scenario.people.len();
+            match *block {
+                ScenarioBlock::SpawnOverTime(idx) => {
+                    self.spawn_over_time[idx].generate(
+                        &mut streams,
+                        &mut scenario,
+                        &mut transit_queries,
+                        map,
+                        timer,
+                    );
+                }
+                ScenarioBlock::BorderSpawnOverTime(idx) => {
+                    self.border_spawn_over_time[idx].generate(
+                        &mut streams,
+                        &mut scenario,
+                        &mut transit_queries,
+                        map,
+                        timer,
+                    );
+                }
+            }
+            for person in &mut scenario.people[start..] {
+                person.provenance = Some(block.provenance());
             }
         }
 
-        timer.start_iter("BorderSpawnOverTime", self.border_spawn_over_time.len());
-        for s in &self.border_spawn_over_time {
-            timer.next();
-            s.spawn_peds(rng, &mut scenario, map, timer);
-            s.spawn_vehicles(
-                s.num_cars,
-                PathConstraints::Car,
-                rng,
-                &mut scenario,
-                map,
-                timer,
-            );
-            s.spawn_vehicles(
-                s.num_bikes,
-                PathConstraints::Bike,
-                rng,
-                &mut scenario,
-                map,
-                timer,
-            );
+        let results = timer.parallelize(
+            "check if transit is worth it",
+            transit_queries,
+            |(idx, start, goal)| (idx, map.should_use_transit(start, goal)),
+        );
+        for (idx, maybe_transit) in results {
+            if let Some((stop1, stop2, route)) = maybe_transit {
+                let trip = &mut scenario.people[idx].trips[0].trip;
+                if let SpawnTrip::JustWalking(start, goal) = trip.clone() {
+                    *trip = SpawnTrip::UsingTransit(start, goal, route, stop1, stop2);
+                }
+            }
+        }
+        for (idx, person) in scenario.people.iter_mut().enumerate() {
+            person.id = PersonID(first_person.0 + idx);
         }
 
         timer.stop(format!("Generating scenario {}", self.scenario_name));
@@ -94,8 +308,16 @@ impl ScenarioGenerator {
             only_seed_buses: None,
             spawn_over_time: vec![SpawnOverTime {
                 num_agents: 100,
+                extra_agent_chance: 0.0,
                 start_time: Time::START_OF_DAY,
                 stop_time: Time::START_OF_DAY + Duration::seconds(5.0),
+                distribution: TimeDistribution::Uniform,
+                jitter: None,
+                goal_retries: 0,
+                origin_weighting: OriginWeighting::Uniform,
+                nearest_border_fallback: false,
+                purpose: TripPurpose::Unknown,
+                origin_weights: Vec::new(),
                 goal: OriginDestination::Anywhere,
                 percent_driving: 0.5,
                 percent_biking: 0.5,
@@ -112,7 +334,14 @@ impl ScenarioGenerator {
                     num_bikes: 10,
                     start_time: Time::START_OF_DAY,
                     stop_time: Time::START_OF_DAY + Duration::seconds(5.0),
+                    distribution: TimeDistribution::Uniform,
                     start_from_border: i.some_outgoing_road(map).unwrap(),
+                    start_from_borders: Vec::new(),
+                    permanent_borders: Vec::new(),
+                    platoon_size: None,
+                    metering: false,
+                    nearest_border_fallback: false,
+                    purpose: TripPurpose::Unknown,
                     goal: OriginDestination::Anywhere,
                     percent_use_transit: 0.5,
                 })
@@ -121,8 +350,16 @@ impl ScenarioGenerator {
         for i in map.all_outgoing_borders() {
             s.spawn_over_time.push(SpawnOverTime {
                 num_agents: 10,
+                extra_agent_chance: 0.0,
                 start_time: Time::START_OF_DAY,
                 stop_time: Time::START_OF_DAY + Duration::seconds(5.0),
+                distribution: TimeDistribution::Uniform,
+                jitter: None,
+                goal_retries: 0,
+                origin_weighting: OriginWeighting::Uniform,
+                nearest_border_fallback: false,
+                purpose: TripPurpose::Unknown,
+                origin_weights: Vec::new(),
                 goal: OriginDestination::EndOfRoad(i.some_incoming_road(map).unwrap()),
                 percent_driving: 0.5,
                 percent_biking: 0.5,
@@ -132,6 +369,135 @@ impl ScenarioGenerator {
         s
     }
 
+    // Only keep the spawning that happens between start and end, measured from midnight. Blocks
+    // partly in the window are trimmed, with their number of agents reduced by the fraction of
+    // their departures that fell in the part kept.
+    pub fn clip_time_window(&self, start: Duration, end: Duration) -> ScenarioGenerator {
+        assert!(start < end);
+        let start = Time::START_OF_DAY + start;
+        let end = Time::START_OF_DAY + end;
+        let mut clipped = ScenarioGenerator {
+            scenario_name: self.scenario_name.clone(),
+            only_seed_buses: self.only_seed_buses.clone(),
+            spawn_over_time: Vec::new(),
+            border_spawn_over_time: Vec::new(),
+        };
+        for s in &self.spawn_over_time {
+            if let Some((start_time, stop_time, scale)) =
+                clip_window(s.start_time, s.stop_time, &s.distribution, start, end)
+            {
+                let mut s = s.clone();
+                s.start_time = start_time;
+                s.stop_time = stop_time;
+                s.set_expected_agents(s.expected_agents() * scale);
+                clipped.spawn_over_time.push(s);
+            }
+        }
+        for s in &self.border_spawn_over_time {
+            if let Some((start_time, stop_time, scale)) =
+                clip_window(s.start_time, s.stop_time, &s.distribution, start, end)
+            {
+                let mut s = s.clone();
+                s.start_time = start_time;
+                s.stop_time = stop_time;
+                s.num_peds = scale_count(s.num_peds, scale);
+                s.num_cars = scale_count(s.num_cars, scale);
+                s.num_bikes = scale_count(s.num_bikes, scale);
+                clipped.border_spawn_over_time.push(s);
+            }
+        }
+        clipped
+    }
+
+    // Finds anything that'd make generating panic partway through.
+    pub fn validate(&self, map: &Map) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        for (idx, s) in self.spawn_over_time.iter().enumerate() {
+            if s.start_time > s.stop_time {
+                problems.push(format!(
+                    "spawn_over_time {}: starts at {}, after it stops at {}",
+                    idx, s.start_time, s.stop_time
+                ));
+            }
+            if let Err(err) = s.distribution.validate() {
+                problems.push(format!("spawn_over_time {}: {}", idx, err));
+            }
+            if s.origin_weights.is_empty() {
+                continue;
+            }
+            for (b, weight) in &s.origin_weights {
+                if map.maybe_get_b(*b).is_none() {
+                    problems.push(format!("spawn_over_time {}: {} doesn't exist", idx, b));
+                }
+                if !weight.is_finite() || *weight < 0.0 {
+                    problems.push(format!(
+                        "spawn_over_time {}: {} has a bad weight {}",
+                        idx, b, weight
+                    ));
+                }
+            }
+            if !s.origin_weights.iter().any(|(_, weight)| *weight > 0.0) {
+                problems.push(format!(
+                    "spawn_over_time {}: origin_weights need at least one positive weight",
+                    idx
+                ));
+            }
+        }
+        for (idx, s) in self.border_spawn_over_time.iter().enumerate() {
+            if s.start_time > s.stop_time {
+                problems.push(format!(
+                    "border_spawn_over_time {}: starts at {}, after it stops at {}",
+                    idx, s.start_time, s.stop_time
+                ));
+            }
+            if let Err(err) = s.distribution.validate() {
+                problems.push(format!("border_spawn_over_time {}: {}", idx, err));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    // Multiplies the number of agents in every block, keeping where and when they spawn the same.
+    pub fn scale_demand(&self, factor: f64) -> ScenarioGenerator {
+        assert!(factor >= 0.0);
+        let mut scaled = self.clone();
+        for s in &mut scaled.spawn_over_time {
+            s.set_expected_agents(s.expected_agents() * factor);
+        }
+        for s in &mut scaled.border_spawn_over_time {
+            s.num_peds = scale_count(s.num_peds, factor);
+            s.num_cars = scale_count(s.num_cars, factor);
+            s.num_bikes = scale_count(s.num_bikes, factor);
+        }
+        scaled
+    }
+
+    // Replaces the raw border IDs with ones that survive re-importing the map. To migrate an old
+    // generator, load it against the map it was made for, call this, and save it again.
+    pub fn make_borders_permanent(&mut self, map: &Map) {
+        for s in &mut self.spawn_over_time {
+            s.goal = s.goal.to_permanent(map);
+        }
+        for s in &mut self.border_spawn_over_time {
+            if s.permanent_borders.is_empty() {
+                s.permanent_borders = if s.start_from_borders.is_empty() {
+                    vec![s.start_from_border.to_permanent(map)]
+                } else {
+                    s.start_from_borders
+                        .iter()
+                        .map(|dr| dr.to_permanent(map))
+                        .collect()
+                };
+            }
+            s.goal = s.goal.to_permanent(map);
+        }
+    }
+
     pub fn empty(name: &str) -> ScenarioGenerator {
         ScenarioGenerator {
             scenario_name: name.to_string(),
@@ -141,6 +507,35 @@ impl ScenarioGenerator {
         }
     }
 
+    // Everybody walks, over the first hour of the day. Each entry is (origin buildings, where they
+    // go, how many people). No origin buildings means anywhere.
+    pub fn pedestrians_between(
+        name: &str,
+        pairs: Vec<(Vec<BuildingID>, OriginDestination, usize)>,
+    ) -> ScenarioGenerator {
+        let mut s = ScenarioGenerator::empty(name);
+        for (origins, goal, num_agents) in pairs {
+            s.spawn_over_time.push(SpawnOverTime {
+                num_agents,
+                extra_agent_chance: 0.0,
+                start_time: Time::START_OF_DAY,
+                stop_time: Time::START_OF_DAY + Duration::hours(1),
+                distribution: TimeDistribution::Uniform,
+                jitter: None,
+                goal_retries: 0,
+                origin_weighting: OriginWeighting::Uniform,
+                nearest_border_fallback: false,
+                purpose: TripPurpose::Unknown,
+                origin_weights: origins.into_iter().map(|b| (b, 1.0)).collect(),
+                goal,
+                percent_driving: 0.0,
+                percent_biking: 0.0,
+                percent_use_transit: 0.0,
+            });
+        }
+        s
+    }
+
     // No border agents here, because making the count work is hard.
     pub fn scaled_run(num_agents: usize) -> ScenarioGenerator {
         ScenarioGenerator {
@@ -148,8 +543,16 @@ impl ScenarioGenerator {
             only_seed_buses: Some(BTreeSet::new()),
             spawn_over_time: vec![SpawnOverTime {
                 num_agents: num_agents,
+                extra_agent_chance: 0.0,
                 start_time: Time::START_OF_DAY,
                 stop_time: Time::START_OF_DAY + Duration::seconds(5.0),
+                distribution: TimeDistribution::Uniform,
+                jitter: None,
+                goal_retries: 0,
+                origin_weighting: OriginWeighting::Uniform,
+                nearest_border_fallback: false,
+                purpose: TripPurpose::Unknown,
+                origin_weights: Vec::new(),
                 goal: OriginDestination::Anywhere,
                 percent_driving: 0.5,
                 percent_biking: 0.5,
@@ -161,30 +564,108 @@ impl ScenarioGenerator {
 }
 
 impl SpawnOverTime {
+    pub fn expected_agents(&self) -> f64 {
+        (self.num_agents as f64) + self.extra_agent_chance
+    }
+
+    // The fractional part becomes the chance of one extra agent, so the mean over many seeds
+    // matches.
+    pub fn set_expected_agents(&mut self, n: f64) {
+        assert!(n >= 0.0);
+        self.num_agents = n.floor() as usize;
+        self.extra_agent_chance = n - n.floor();
+    }
+
+    fn generate(
+        &self,
+        streams: &mut RngStreams,
+        scenario: &mut Scenario,
+        transit_queries: &mut Vec<TransitQuery>,
+        map: &Map,
+        timer: &mut Timer,
+    ) {
+        // extra_agent_chance might've been edited by hand to something outside [0, 1), which
+        // gen_bool can't take
+        let expected = self.expected_agents().max(0.0);
+        let mut num_agents = expected.floor() as usize;
+        let chance = expected - expected.floor();
+        if chance > 0.0 && streams.modes.gen_bool(chance) {
+            num_agents += 1;
+        }
+        let goal = match self.goal.resolve(map, timer) {
+            Some(goal) => goal,
+            None => {
+                return;
+            }
+        };
+        timer.start_iter("SpawnOverTime each agent", num_agents);
+        for _ in 0..num_agents {
+            timer.next();
+            self.spawn_agent(&goal, streams, scenario, transit_queries, map, timer);
+        }
+    }
+
     fn spawn_agent(
         &self,
-        rng: &mut XorShiftRng,
+        goal: &OriginDestination,
+        streams: &mut RngStreams,
         scenario: &mut Scenario,
+        transit_queries: &mut Vec<TransitQuery>,
         map: &Map,
         timer: &mut Timer,
     ) {
-        let depart = rand_time(rng, self.start_time, self.stop_time);
-        // Note that it's fine for agents to start/end at the same building. Later we might
-        // want a better assignment of people per household, or workers per office building.
-        let from_bldg = map.all_buildings().choose(rng).unwrap().id;
+        // validate catches this before generating
+        let depart = match rand_time(
+            &mut streams.times,
+            self.start_time,
+            self.stop_time,
+            &self.distribution,
+            self.jitter,
+        ) {
+            Ok(t) => t,
+            Err(err) => {
+                timer.warn(format!("Not spawning anyone: {}", err));
+                return;
+            }
+        };
+        // Note that it's fine for agents to start/end at the same building, unless goal_retries
+        // is set. Later we might want a better assignment of people per household, or workers
+        // per office building.
+        let from_bldg = if self.origin_weights.is_empty() {
+            self.origin_weighting.pick_bldg(map, &mut streams.goals)
+        } else {
+            self.origin_weights
+                .choose_weighted(&mut streams.goals, |(_, weight)| *weight)
+                .expect("validate makes sure origin_weights has a positive weight")
+                .0
+        };
         let id = PersonID(scenario.people.len());
 
-        if rng.gen_bool(self.percent_driving) {
-            if let Some(goal) = self
-                .goal
-                .pick_driving_goal(PathConstraints::Car, map, rng, timer)
-            {
+        if streams.modes.gen_bool(self.percent_driving) {
+            if let Some(goal) = self.retry_goal(
+                &mut streams.goals,
+                timer,
+                |goal| *goal == DrivingGoal::ParkNear(from_bldg),
+                |rng, timer| {
+                    goal.pick_driving_goal(
+                        PathConstraints::Car,
+                        self.nearest_border_fallback,
+                        &self.origin_weighting,
+                        map,
+                        rng,
+                        timer,
+                    )
+                },
+            ) {
                 scenario.people.push(PersonSpec {
                     id,
                     orig_id: None,
+                    provenance: None,
+                    label: None,
                     trips: vec![IndividTrip {
                         depart,
                         trip: SpawnTrip::UsingParkedCar(from_bldg, goal),
+                        purpose: self.purpose,
                     }],
                 });
                 return;
@@ -193,53 +674,65 @@ impl SpawnOverTime {
 
         let start_spot = SidewalkSpot::building(from_bldg, map);
 
-        if rng.gen_bool(self.percent_biking) {
-            if let Some(goal) = self
-                .goal
-                .pick_driving_goal(PathConstraints::Bike, map, rng, timer)
-            {
+        if streams.modes.gen_bool(self.percent_biking) {
+            if let Some(goal) = self.retry_goal(
+                &mut streams.goals,
+                timer,
+                |goal| *goal == DrivingGoal::ParkNear(from_bldg),
+                |rng, timer| {
+                    goal.pick_driving_goal(
+                        PathConstraints::Bike,
+                        self.nearest_border_fallback,
+                        &self.origin_weighting,
+                        map,
+                        rng,
+                        timer,
+                    )
+                },
+            ) {
                 scenario.people.push(PersonSpec {
                     id,
                     orig_id: None,
+                    provenance: None,
+                    label: None,
                     trips: vec![IndividTrip {
                         depart,
                         trip: SpawnTrip::UsingBike(start_spot, goal),
+                        purpose: self.purpose,
                     }],
                 });
                 return;
             }
         }
 
-        if let Some(goal) = self.goal.pick_walking_goal(map, rng, timer) {
+        if let Some(goal) = self.retry_goal(
+            &mut streams.goals,
+            timer,
+            |goal| *goal == start_spot,
+            |rng, timer| goal.pick_walking_goal(&self.origin_weighting, map, rng, timer),
+        ) {
             if start_spot == goal {
                 timer.warn("Skipping walking trip between same two buildings".to_string());
                 return;
             }
 
-            if rng.gen_bool(self.percent_use_transit) {
-                // TODO This throws away some work. It also sequentially does expensive
-                // work right here.
-                if let Some((stop1, stop2, route)) =
-                    map.should_use_transit(start_spot.sidewalk_pos, goal.sidewalk_pos)
-                {
-                    scenario.people.push(PersonSpec {
-                        id,
-                        orig_id: None,
-                        trips: vec![IndividTrip {
-                            depart,
-                            trip: SpawnTrip::UsingTransit(start_spot, goal, route, stop1, stop2),
-                        }],
-                    });
-                    return;
-                }
+            if streams.modes.gen_bool(self.percent_use_transit) {
+                transit_queries.push((
+                    scenario.people.len(),
+                    start_spot.sidewalk_pos,
+                    goal.sidewalk_pos,
+                ));
             }
 
             scenario.people.push(PersonSpec {
                 id,
                 orig_id: None,
+                provenance: None,
+                label: None,
                 trips: vec![IndividTrip {
                     depart,
                     trip: SpawnTrip::JustWalking(start_spot, goal),
+                    purpose: self.purpose,
                 }],
             });
             return;
@@ -247,13 +740,87 @@ impl SpawnOverTime {
 
         timer.warn(format!("Couldn't fulfill {:?} at all", self));
     }
+
+    // Picks a goal, trying again up to goal_retries times while it matches the origin. If a retry
+    // doesn't find anything, the last goal found is used.
+    fn retry_goal<T, F: Fn(&mut XorShiftRng, &mut Timer) -> Option<T>>(
+        &self,
+        rng: &mut XorShiftRng,
+        timer: &mut Timer,
+        is_origin: impl Fn(&T) -> bool,
+        pick: F,
+    ) -> Option<T> {
+        let mut goal = pick(rng, timer)?;
+        for _ in 0..self.goal_retries {
+            if !is_origin(&goal) {
+                break;
+            }
+            match pick(rng, timer) {
+                Some(g) => {
+                    goal = g;
+                }
+                None => break,
+            }
+        }
+        Some(goal)
+    }
 }
 
 impl BorderSpawnOverTime {
+    fn generate(
+        &self,
+        streams: &mut RngStreams,
+        scenario: &mut Scenario,
+        transit_queries: &mut Vec<TransitQuery>,
+        map: &Map,
+        timer: &mut Timer,
+    ) {
+        let goal = match self.goal.resolve(map, timer) {
+            Some(goal) => goal,
+            None => {
+                return;
+            }
+        };
+        // Resolve once, so problems with permanent_borders are only reported once
+        let borders = self.borders(map, timer);
+        self.spawn_peds(
+            &goal,
+            &borders,
+            streams,
+            scenario,
+            transit_queries,
+            map,
+            timer,
+        );
+        self.spawn_vehicles(
+            &goal,
+            &borders,
+            self.num_cars,
+            PathConstraints::Car,
+            streams,
+            scenario,
+            map,
+            timer,
+        );
+        self.spawn_vehicles(
+            &goal,
+            &borders,
+            self.num_bikes,
+            PathConstraints::Bike,
+            streams,
+            scenario,
+            map,
+            timer,
+        );
+    }
+
     fn spawn_peds(
         &self,
-        rng: &mut XorShiftRng,
+        goal: &OriginDestination,
+        borders: &[DirectedRoadID],
+        streams: &mut RngStreams,
         scenario: &mut Scenario,
+        transit_queries: &mut Vec<TransitQuery>,
         map: &Map,
         timer: &mut Timer,
     ) {
@@ -261,52 +828,59 @@ impl BorderSpawnOverTime {
             return;
         }
 
-        let start = if let Some(s) =
-            SidewalkSpot::start_at_border(self.start_from_border.src_i(map), None, map)
-        {
-            s
-        } else {
-            timer.warn(format!(
-                "Can't start_at_border for {} without sidewalk",
-                self.start_from_border
-            ));
+        let mut starts = Vec::new();
+        for dr in borders {
+            if let Some(s) = SidewalkSpot::start_at_border(dr.src_i(map), None, map) {
+                starts.push(s);
+            } else {
+                timer.warn(format!("Can't start_at_border for {} without sidewalk", dr));
+            }
+        }
+        if starts.is_empty() {
             return;
-        };
+        }
 
         for _ in 0..self.num_peds {
-            let depart = rand_time(rng, self.start_time, self.stop_time);
+            // validate catches this before generating
+            let depart = match rand_time(
+                &mut streams.times,
+                self.start_time,
+                self.stop_time,
+                &self.distribution,
+                None,
+            ) {
+                Ok(t) => t,
+                Err(err) => {
+                    timer.warn(format!("Not spawning anyone: {}", err));
+                    return;
+                }
+            };
+            let start = if starts.len() == 1 {
+                &starts[0]
+            } else {
+                starts.choose(&mut streams.goals).unwrap()
+            };
             let id = PersonID(scenario.people.len());
-            if let Some(goal) = self.goal.pick_walking_goal(map, rng, timer) {
-                if rng.gen_bool(self.percent_use_transit) {
-                    // TODO This throws away some work. It also sequentially does expensive
-                    // work right here.
-                    if let Some((stop1, stop2, route)) =
-                        map.should_use_transit(start.sidewalk_pos, goal.sidewalk_pos)
-                    {
-                        scenario.people.push(PersonSpec {
-                            id,
-                            orig_id: None,
-                            trips: vec![IndividTrip {
-                                depart,
-                                trip: SpawnTrip::UsingTransit(
-                                    start.clone(),
-                                    goal,
-                                    route,
-                                    stop1,
-                                    stop2,
-                                ),
-                            }],
-                        });
-                        continue;
-                    }
+            if let Some(goal) =
+                goal.pick_walking_goal(&OriginWeighting::Uniform, map, &mut streams.goals, timer)
+            {
+                if streams.modes.gen_bool(self.percent_use_transit) {
+                    transit_queries.push((
+                        scenario.people.len(),
+                        start.sidewalk_pos,
+                        goal.sidewalk_pos,
+                    ));
                 }
 
                 scenario.people.push(PersonSpec {
                     id,
                     orig_id: None,
+                    provenance: None,
+                    label: None,
                     trips: vec![IndividTrip {
                         depart,
                         trip: SpawnTrip::JustWalking(start.clone(), goal),
+                        purpose: self.purpose,
                     }],
                 });
             }
@@ -315,79 +889,200 @@ impl BorderSpawnOverTime {
 
     fn spawn_vehicles(
         &self,
+        goal: &OriginDestination,
+        borders: &[DirectedRoadID],
         num: usize,
         constraints: PathConstraints,
-        rng: &mut XorShiftRng,
+        streams: &mut RngStreams,
         scenario: &mut Scenario,
         map: &Map,
         timer: &mut Timer,
     ) {
-        for _ in 0..num {
-            let depart = rand_time(rng, self.start_time, self.stop_time);
-            if let Some(goal) = self.goal.pick_driving_goal(constraints, map, rng, timer) {
-                let id = PersonID(scenario.people.len());
-                scenario.people.push(PersonSpec {
-                    id,
-                    orig_id: None,
-                    trips: vec![IndividTrip {
-                        depart,
-                        trip: SpawnTrip::FromBorder {
-                            dr: self.start_from_border,
-                            goal,
-                            is_bike: constraints == PathConstraints::Bike,
-                            origin: None,
-                        },
-                    }],
-                });
+        if borders.is_empty() {
+            return;
+        }
+        let platoon_size = self.platoon_size.unwrap_or(1).max(1);
+        let mut remaining = num;
+        while remaining > 0 {
+            // The last platoon might be smaller
+            let size = platoon_size.min(remaining);
+            remaining -= size;
+
+            // validate catches this before generating
+            let depart = match rand_time(
+                &mut streams.times,
+                self.start_time,
+                self.stop_time,
+                &self.distribution,
+                None,
+            ) {
+                Ok(t) => t,
+                Err(err) => {
+                    timer.warn(format!("Not spawning anyone: {}", err));
+                    return;
+                }
+            };
+            let dr = if borders.len() == 1 {
+                borders[0]
+            } else {
+                *borders.choose(&mut streams.goals).unwrap()
+            };
+            if let Some(goal) = goal.pick_driving_goal(
+                constraints,
+                self.nearest_border_fallback,
+                &OriginWeighting::Uniform,
+                map,
+                &mut streams.goals,
+                timer,
+            ) {
+                for _ in 0..size {
+                    let id = PersonID(scenario.people.len());
+                    scenario.people.push(PersonSpec {
+                        id,
+                        orig_id: None,
+                        provenance: None,
+                        label: None,
+                        trips: vec![IndividTrip {
+                            depart,
+                            trip: SpawnTrip::FromBorder {
+                                dr,
+                                goal: goal.clone(),
+                                is_bike: constraints == PathConstraints::Bike,
+                                origin: None,
+                                give_up_at: if self.metering {
+                                    Some(self.stop_time)
+                                } else {
+                                    None
+                                },
+                            },
+                            purpose: self.purpose,
+                        }],
+                    });
+                }
             }
         }
     }
+
+    fn borders(&self, map: &Map, timer: &mut Timer) -> Vec<DirectedRoadID> {
+        if !self.permanent_borders.is_empty() {
+            let mut borders = Vec::new();
+            for orig in &self.permanent_borders {
+                match orig.from_permanent(map) {
+                    Ok(dr) => borders.push(dr),
+                    Err(err) => timer.warn(format!("Skipping border {:?}: {}", orig, err)),
+                }
+            }
+            return borders;
+        }
+        if self.start_from_borders.is_empty() {
+            vec![self.start_from_border]
+        } else {
+            self.start_from_borders.clone()
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub enum OriginDestination {
     Anywhere,
     EndOfRoad(DirectedRoadID),
+    // Like EndOfRoad, but survives re-importing the map
+    EndOfOriginalRoad(OriginalDirectedRoad),
+    // Everybody goes to exactly this building, like a stadium during an event
     GotoBldg(BuildingID),
+    // Everybody walks to this bus stop, to concentrate demand on the routes serving it. There's
+    // no driving or biking to a stop, so people who'd use a car or bike walk instead.
+    TransitStop(BusStopID),
 }
 
 impl OriginDestination {
+    fn to_permanent(&self, map: &Map) -> OriginDestination {
+        if let OriginDestination::EndOfRoad(dr) = self {
+            OriginDestination::EndOfOriginalRoad(dr.to_permanent(map))
+        } else {
+            self.clone()
+        }
+    }
+
+    // Turns EndOfOriginalRoad into EndOfRoad for this map. This looks through every road, so do it
+    // once per block, not per agent. The pick methods only take the result.
+    fn resolve(&self, map: &Map, timer: &mut Timer) -> Option<OriginDestination> {
+        if let OriginDestination::EndOfOriginalRoad(orig) = self {
+            match orig.from_permanent(map) {
+                Ok(dr) => Some(OriginDestination::EndOfRoad(dr)),
+                Err(err) => {
+                    timer.warn(format!("Can't go to {:?}: {}", orig, err));
+                    None
+                }
+            }
+        } else {
+            Some(self.clone())
+        }
+    }
+
     fn pick_driving_goal(
         &self,
         constraints: PathConstraints,
+        nearest_border_fallback: bool,
+        weighting: &OriginWeighting,
         map: &Map,
         rng: &mut XorShiftRng,
         timer: &mut Timer,
     ) -> Option<DrivingGoal> {
-        match self {
-            OriginDestination::Anywhere => Some(DrivingGoal::ParkNear(
-                map.all_buildings().choose(rng).unwrap().id,
-            )),
-            OriginDestination::GotoBldg(b) => Some(DrivingGoal::ParkNear(*b)),
-            OriginDestination::EndOfRoad(dr) => {
-                let goal = DrivingGoal::end_at_border(*dr, constraints, None, map);
-                if goal.is_none() {
+        match *self {
+            OriginDestination::Anywhere => {
+                Some(DrivingGoal::ParkNear(weighting.pick_bldg(map, rng)))
+            }
+            OriginDestination::GotoBldg(b) => {
+                if map.maybe_get_b(b).is_none() {
                     timer.warn(format!(
-                        "Can't spawn a {:?} ending at border {}; no appropriate lanes there",
-                        constraints, dr
+                        "Can't spawn a {:?} going to {}; it doesn't exist",
+                        constraints, b
                     ));
+                    return None;
                 }
-                goal
+                Some(DrivingGoal::ParkNear(b))
+            }
+            OriginDestination::TransitStop(_) => None,
+            OriginDestination::EndOfOriginalRoad(_) => unreachable!(),
+            OriginDestination::EndOfRoad(dr) => {
+                // Spread vehicles over all of the lanes leading to the border, instead of
+                // funneling everybody into one. Bikes stick to bike lanes when they exist.
+                if let Some(l) = dr.lanes(constraints, map).choose(rng) {
+                    return Some(DrivingGoal::Border(dr.dst_i(map), *l, None));
+                }
+                if nearest_border_fallback {
+                    if let Some((i, l)) = nearest_border_with_lane(dr.dst_i(map), constraints, map)
+                    {
+                        timer.warn(format!(
+                            "No {:?} lanes lead to border {}; going to {} instead",
+                            constraints,
+                            dr.dst_i(map),
+                            i
+                        ));
+                        return Some(DrivingGoal::Border(i, l, None));
+                    }
+                }
+                timer.warn(format!(
+                    "Can't spawn a {:?} ending at border {}; no appropriate lanes there",
+                    constraints, dr
+                ));
+                None
             }
         }
     }
 
     fn pick_walking_goal(
         &self,
+        weighting: &OriginWeighting,
         map: &Map,
         rng: &mut XorShiftRng,
         timer: &mut Timer,
     ) -> Option<SidewalkSpot> {
-        match self {
-            OriginDestination::Anywhere => Some(SidewalkSpot::building(
-                map.all_buildings().choose(rng).unwrap().id,
-                map,
-            )),
+        match *self {
+            OriginDestination::Anywhere => {
+                Some(SidewalkSpot::building(weighting.pick_bldg(map, rng), map))
+            }
             OriginDestination::EndOfRoad(dr) => {
                 let goal = SidewalkSpot::end_at_border(dr.dst_i(map), None, map);
                 if goal.is_none() {
@@ -395,12 +1090,379 @@ impl OriginDestination {
                 }
                 goal
             }
-            OriginDestination::GotoBldg(b) => Some(SidewalkSpot::building(*b, map)),
+            OriginDestination::GotoBldg(b) => {
+                if map.maybe_get_b(b).is_none() {
+                    timer.warn(format!("Can't walk to {}; it doesn't exist", b));
+                    return None;
+                }
+                Some(SidewalkSpot::building(b, map))
+            }
+            OriginDestination::TransitStop(bs) => {
+                if map.maybe_get_bs(bs).is_none() {
+                    timer.warn(format!("Can't walk to {}; it doesn't exist", bs));
+                    return None;
+                }
+                Some(SidewalkSpot::bus_stop(bs, map))
+            }
+            OriginDestination::EndOfOriginalRoad(_) => unreachable!(),
+        }
+    }
+}
+
+// The closest outgoing border to the given intersection that has some lane the constraints allow
+fn nearest_border_with_lane(
+    from: IntersectionID,
+    constraints: PathConstraints,
+    map: &Map,
+) -> Option<(IntersectionID, LaneID)> {
+    let pt = map.get_i(from).polygon.center();
+    map.all_outgoing_borders()
+        .into_iter()
+        .filter(|i| i.id != from)
+        .filter_map(|i| {
+            let l = i
+                .incoming_lanes
+                .iter()
+                .find(|l| constraints.can_use(map.get_l(**l), map))?;
+            Some((i.id, *l, i.polygon.center().dist_to(pt)))
+        })
+        .min_by_key(|(_, _, dist)| *dist)
+        .map(|(i, l, _)| (i, l))
+}
+
+// How many times rand_time draws from a Normal before giving up on landing in the window
+const MAX_NORMAL_SAMPLES: usize = 100;
+
+// Returns the part of [low, high] inside [start, end), and the fraction of departures from the
+// original that land there. None if nothing's left.
+fn clip_window(
+    low: Time,
+    high: Time,
+    distribution: &TimeDistribution,
+    start: Time,
+    end: Time,
+) -> Option<(Time, Time, f64)> {
+    // Everybody leaves at one instant
+    if low == high {
+        if low >= start && low < end {
+            return Some((low, high, 1.0));
+        }
+        return None;
+    }
+    let new_low = low.max(start);
+    let new_high = high.min(end);
+    if new_low >= new_high {
+        return None;
+    }
+    let uniform = (new_high - new_low) / (high - low);
+    let fraction = match distribution {
+        TimeDistribution::Uniform => uniform,
+        TimeDistribution::Normal { mean, std_dev } => {
+            if *std_dev == Duration::ZERO {
+                // Everybody leaves at the mean, or as close to it as the window allows
+                let t = (*mean).max(low).min(high);
+                if t >= new_low && t <= new_high {
+                    1.0
+                } else {
+                    0.0
+                }
+            } else {
+                let cdf = |t: Time| normal_cdf((t - *mean) / *std_dev);
+                let total = cdf(high) - cdf(low);
+                // rand_time clamps in this case, so there's no good answer. Pretend it's uniform.
+                if total < 1e-9 {
+                    uniform
+                } else {
+                    (cdf(new_high) - cdf(new_low)) / total
+                }
+            }
+        }
+    };
+    if fraction <= 0.0 {
+        return None;
+    }
+    Some((new_low, new_high, fraction.min(1.0)))
+}
+
+// The standard normal CDF. Uses the erf approximation from Abramowitz and Stegun (7.1.26), which
+// is off by at most 1.5e-7.
+fn normal_cdf(x: f64) -> f64 {
+    let z = x.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.3275911 * z);
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let erf = 1.0 - poly * (-z * z).exp();
+    if x >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+fn scale_count(count: usize, scale: f64) -> usize {
+    ((count as f64) * scale).round() as usize
+}
+
+// Independent random streams for generating and instantiating a scenario. Every block of a
+// ScenarioGenerator gets its own set, seeded from one u64 and the block, and the streams within
+// a set are forked in a fixed order. A new random draw in one area only shifts the results of
+// that stream in that block, so it doesn't change everybody else's departure times, destinations,
+// or vehicles.
+pub(crate) struct RngStreams {
+    // Departure times
+    pub times: XorShiftRng,
+    // Picking modes, and how many agents to spawn
+    pub modes: XorShiftRng,
+    // Origins and destinations
+    pub goals: XorShiftRng,
+    // Vehicle specs
+    pub vehicles: XorShiftRng,
+    // Walking speeds
+    pub ped_speeds: XorShiftRng,
+    // Forked once per trip, for the choices turning it into a TripSpec
+    pub trips: XorShiftRng,
+}
+
+impl RngStreams {
+    // People who didn't come from a block, like imported ones, share the None set.
+    pub fn for_block(seed: u64, block: Option<ScenarioBlock>) -> RngStreams {
+        let key = match block {
+            None => 0,
+            Some(ScenarioBlock::SpawnOverTime(idx)) => 2 * idx + 1,
+            Some(ScenarioBlock::BorderSpawnOverTime(idx)) => 2 * idx + 2,
+        };
+        let mut master = XorShiftRng::seed_from_u64(seed ^ (key as u64));
+        // Never reorder these, or every scenario changes. New streams go at the end.
+        RngStreams {
+            times: fork_stream(&mut master),
+            modes: fork_stream(&mut master),
+            goals: fork_stream(&mut master),
+            vehicles: fork_stream(&mut master),
+            ped_speeds: fork_stream(&mut master),
+            trips: fork_stream(&mut master),
+        }
+    }
+}
+
+// Unlike abstutil::fork_rng, use a full seed. That only has 256 outcomes, so two streams could
+// easily wind up identical.
+fn fork_stream(master: &mut XorShiftRng) -> XorShiftRng {
+    let mut seed = [0; 16];
+    master.fill(&mut seed);
+    XorShiftRng::from_seed(seed)
+}
+
+// (index into Scenario::people, start, goal) for someone who might take transit
+type TransitQuery = (usize, Position, Position);
+
+fn rand_time(
+    rng: &mut XorShiftRng,
+    low: Time,
+    high: Time,
+    distribution: &TimeDistribution,
+    jitter: Option<Duration>,
+) -> Result<Time, String> {
+    if high < low {
+        return Err(format!("the time window {} to {} is backwards", low, high));
+    }
+    // Everybody leaving at once, like a bus unloading
+    if high == low {
+        return Ok(low);
+    }
+    let t = match distribution {
+        TimeDistribution::Uniform => {
+            Time::START_OF_DAY
+                + Duration::seconds(rng.gen_range(low.inner_seconds(), high.inner_seconds()))
+        }
+        TimeDistribution::Normal { mean, std_dev } => {
+            let normal = Normal::new(mean.inner_seconds(), std_dev.inner_seconds())
+                .map_err(|err| format!("bad normal distribution: {}", err))?;
+            let (low_secs, high_secs) = (low.inner_seconds(), high.inner_seconds());
+            let mut secs = normal.sample(rng);
+            for _ in 1..MAX_NORMAL_SAMPLES {
+                if secs >= low_secs && secs <= high_secs {
+                    break;
+                }
+                secs = normal.sample(rng);
+            }
+            Time::START_OF_DAY + Duration::seconds(secs.max(low_secs).min(high_secs))
         }
+    };
+    // Only touch the RNG when asked to, so existing scenarios don't change
+    match jitter {
+        Some(max) if max > Duration::ZERO => {
+            Ok((t + Duration::seconds(rng.gen_range(0.0, max.inner_seconds()))).min(high))
+        }
+        _ => Ok(t),
     }
 }
 
-fn rand_time(rng: &mut XorShiftRng, low: Time, high: Time) -> Time {
-    assert!(high > low);
-    Time::START_OF_DAY + Duration::seconds(rng.gen_range(low.inner_seconds(), high.inner_seconds()))
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rand_time_instant() {
+        let mut rng = XorShiftRng::from_seed([42; 16]);
+        let t = Time::START_OF_DAY + Duration::hours(8);
+        for distribution in vec![
+            TimeDistribution::Uniform,
+            TimeDistribution::Normal {
+                mean: t,
+                std_dev: Duration::minutes(5),
+            },
+        ] {
+            for _ in 0..100 {
+                assert_eq!(rand_time(&mut rng, t, t, &distribution, None), Ok(t));
+            }
+        }
+    }
+    #[test]
+    fn test_rand_time_jitter_stays_in_window() {
+        let mut rng = XorShiftRng::from_seed([42; 16]);
+        let low = Time::START_OF_DAY;
+        let high = low + Duration::seconds(5.0);
+        for _ in 0..100 {
+            let t = rand_time(
+                &mut rng,
+                low,
+                high,
+                &TimeDistribution::Uniform,
+                Some(Duration::seconds(1.0)),
+            )
+            .unwrap();
+            assert!(t >= low && t <= high);
+        }
+    }
+    #[test]
+    fn test_rand_time_bad_normal() {
+        let mut rng = XorShiftRng::from_seed([42; 16]);
+        let low = Time::START_OF_DAY;
+        let high = low + Duration::hours(1);
+        let normal = TimeDistribution::Normal {
+            mean: low,
+            std_dev: Duration::seconds(-300.0),
+        };
+        assert!(normal.validate().is_err());
+        assert!(rand_time(&mut rng, low, high, &normal, None).is_err());
+        assert!(rand_time(&mut rng, high, low, &TimeDistribution::Uniform, None).is_err());
+    }
+    #[test]
+    fn test_rng_streams_are_independent() {
+        let mut a = RngStreams::for_block(42, None);
+        let mut b = RngStreams::for_block(42, None);
+        // An extra draw from one stream doesn't affect the others
+        let _: f64 = a.goals.gen();
+        for _ in 0..10 {
+            assert_eq!(a.times.gen::<u64>(), b.times.gen::<u64>());
+            assert_eq!(a.vehicles.gen::<u64>(), b.vehicles.gen::<u64>());
+        }
+        assert_ne!(a.times.gen::<u64>(), a.modes.gen::<u64>());
+    }
+    #[test]
+    fn test_rng_streams_per_block() {
+        let draw = |block| RngStreams::for_block(42, block).times.gen::<u64>();
+        let first = draw(Some(ScenarioBlock::SpawnOverTime(0)));
+        assert_eq!(first, draw(Some(ScenarioBlock::SpawnOverTime(0))));
+        assert_ne!(first, draw(Some(ScenarioBlock::SpawnOverTime(1))));
+        assert_ne!(first, draw(Some(ScenarioBlock::BorderSpawnOverTime(0))));
+        assert_ne!(first, draw(None));
+    }
+    #[test]
+    fn test_block_provenance() {
+        for block in vec![
+            ScenarioBlock::SpawnOverTime(3),
+            ScenarioBlock::BorderSpawnOverTime(0),
+        ] {
+            assert_eq!(
+                ScenarioBlock::from_provenance(&block.provenance()),
+                Some(block)
+            );
+        }
+        assert_eq!(ScenarioBlock::from_provenance("burst spawn at 7:00"), None);
+    }
+    #[test]
+    fn test_clip_window() {
+        let t = |hours| Time::START_OF_DAY + Duration::hours(hours);
+        let u = &TimeDistribution::Uniform;
+        // Entirely inside, outside, and overlapping the start of the window
+        assert_eq!(
+            clip_window(t(7), t(8), u, t(6), t(10)),
+            Some((t(7), t(8), 1.0))
+        );
+        assert_eq!(clip_window(t(12), t(14), u, t(6), t(10)), None);
+        assert_eq!(
+            clip_window(t(4), t(8), u, t(6), t(10)),
+            Some((t(6), t(8), 0.5))
+        );
+        // The end of the window is exclusive
+        assert_eq!(clip_window(t(10), t(10), u, t(6), t(10)), None);
+        assert_eq!(
+            clip_window(t(6), t(6), u, t(6), t(10)),
+            Some((t(6), t(6), 1.0))
+        );
+        assert_eq!(scale_count(100, 0.5), 50);
+    }
+    #[test]
+    fn test_clip_window_normal() {
+        let t = |hours| Time::START_OF_DAY + Duration::hours(hours);
+        let normal = TimeDistribution::Normal {
+            mean: t(8),
+            std_dev: Duration::minutes(30),
+        };
+        // Cutting the window at the mean keeps half of the departures
+        let (low, high, fraction) = clip_window(t(6), t(10), &normal, t(8), t(12)).unwrap();
+        assert_eq!((low, high), (t(8), t(10)));
+        assert!((fraction - 0.5).abs() < 1e-6);
+        // Most departures are within an hour of the mean, even though that's half the window
+        let (_, _, fraction) = clip_window(t(6), t(10), &normal, t(7), t(9)).unwrap();
+        assert!(fraction > 0.95);
+    }
+    #[test]
+    fn test_rand_time_normal_doesnt_pile_up_at_edges() {
+        let mut rng = XorShiftRng::from_seed([42; 16]);
+        let low = Time::START_OF_DAY + Duration::hours(7);
+        let high = Time::START_OF_DAY + Duration::hours(8);
+        let normal = TimeDistribution::Normal {
+            mean: low,
+            std_dev: Duration::hours(1),
+        };
+        for _ in 0..100 {
+            let t = rand_time(&mut rng, low, high, &normal, None).unwrap();
+            assert!(t > low && t < high);
+        }
+    }
+    #[test]
+    fn test_retry_goal_keeps_last_goal() {
+        let mut s = ScenarioGenerator::scaled_run(5).spawn_over_time[0].clone();
+        s.goal_retries = 3;
+        let mut rng = XorShiftRng::from_seed([42; 16]);
+        let mut timer = Timer::throwaway();
+        // The first pick matches the origin, and the retry finds nothing
+        let picks = std::cell::Cell::new(0);
+        let goal = s.retry_goal(
+            &mut rng,
+            &mut timer,
+            |goal| *goal == 0,
+            |_, _| {
+                picks.set(picks.get() + 1);
+                if picks.get() == 1 {
+                    Some(0)
+                } else {
+                    None
+                }
+            },
+        );
+        assert_eq!(goal, Some(0));
+        assert_eq!(picks.get(), 2);
+    }
+    #[test]
+    fn test_fractional_agents() {
+        let scaled = ScenarioGenerator::scaled_run(5).scale_demand(0.5);
+        let s = &scaled.spawn_over_time[0];
+        assert_eq!(s.num_agents, 2);
+        assert_eq!(s.extra_agent_chance, 0.5);
+        assert_eq!(s.expected_agents(), 2.5);
+    }
 }
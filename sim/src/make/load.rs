@@ -1,5 +1,6 @@
-use crate::{AlertHandler, Scenario, Sim, SimOptions};
+use crate::{AlertHandler, DrivingConfig, Scenario, Sim, SimOptions, SteadyStateThresholds};
 use abstutil::CmdArgs;
+use geom::Duration;
 use map_model::{Map, MapEdits};
 use rand::SeedableRng;
 use rand_xorshift::XorShiftRng;
@@ -47,6 +48,17 @@ impl SimFlags {
                     })
                     .unwrap_or(AlertHandler::Print),
                 pathfinding_upfront: args.enabled("--pathfinding_upfront"),
+                driving_config: DrivingConfig::default(),
+                completed_trips_period: args
+                    .optional_parse("--completed_trips_period", Duration::parse)
+                    .map(|dt| {
+                        if dt <= Duration::ZERO {
+                            panic!("Bad --completed_trips_period={}. Must be positive", dt);
+                        }
+                        dt
+                    })
+                    .unwrap_or(Duration::minutes(1)),
+                steady_state: SteadyStateThresholds::default(),
             },
         }
     }
@@ -4,8 +4,12 @@ mod scenario;
 mod spawner;
 
 pub use self::generator::{
-    BorderSpawnOverTime, OriginDestination, ScenarioGenerator, SpawnOverTime,
+    BorderSpawnOverTime, OriginDestination, OriginWeighting, ScenarioBlock, ScenarioGenerator,
+    SpawnOverTime, TimeDistribution,
 };
 pub use self::load::SimFlags;
-pub use self::scenario::{IndividTrip, OffMapLocation, PersonSpec, Scenario, SpawnTrip};
+pub use self::scenario::{
+    FeasibilityReport, IndividTrip, InstantiateSummary, OffMapLocation, ParkedCarCount,
+    ParkedVehicleSpec, PersonSpec, Scenario, SeedParkedCars, SpawnTrip, TimedEvent,
+};
 pub use self::spawner::{TripSpawner, TripSpec};
@@ -1,12 +1,15 @@
+use crate::make::generator::RngStreams;
+use crate::make::ScenarioBlock;
 use crate::{
-    CarID, DrivingGoal, OrigPersonID, ParkingSpot, PersonID, SidewalkPOI, SidewalkSpot, Sim,
-    TripEndpoint, TripMode, TripSpec, Vehicle, VehicleSpec, VehicleType, BIKE_LENGTH,
-    MAX_CAR_LENGTH, MIN_CAR_LENGTH,
+    DrivingGoal, OrigPersonID, ParkingSimState, ParkingSpot, PersonID, SidewalkPOI, SidewalkSpot,
+    Sim, TripEndpoint, TripMode, TripPurpose, TripSpec, Vehicle, VehicleSpec, VehicleType,
+    BIKE_LENGTH, MAX_CAR_LENGTH, MIN_CAR_LENGTH,
 };
 use abstutil::{prettyprint_usize, Counter, Timer};
 use geom::{Distance, Duration, LonLat, Speed, Time};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, DirectedRoadID, Map, PathConstraints, Position, RoadID,
+    BuildingID, BusRouteID, BusStopID, DirectedRoadID, LaneID, Map, PathConstraints, PathRequest,
+    PathStep, Position, RoadID,
 };
 use rand::seq::SliceRandom;
 use rand::{Rng, SeedableRng};
@@ -14,6 +17,15 @@ use rand_xorshift::XorShiftRng;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 
+// Each bike's max speed is picked uniformly from this range, so they don't all bunch up. 8 and 16
+// mph.
+const MIN_BIKE_SPEED: Speed = Speed::const_meters_per_second(3.57632);
+const MAX_BIKE_SPEED: Speed = Speed::const_meters_per_second(7.15264);
+
+// Only first trips departing this soon after midnight are candidates for Scenario::warmup. Trips
+// later in the day wouldn't be underway yet.
+const WARMUP_HORIZON: Duration = Duration::const_seconds(3600.0);
+
 // How to start a simulation.
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Scenario {
@@ -23,6 +35,145 @@ pub struct Scenario {
     pub people: Vec<PersonSpec>,
     // None means seed all buses. Otherwise the route name must be present here.
     pub only_seed_buses: Option<BTreeSet<String>>,
+    pub seed_parked_cars: SeedParkedCars,
+    // Scripted disruptions happening on top of everyone's trips
+    pub events: Vec<TimedEvent>,
+    // If set, this fraction of people whose first trip departs within WARMUP_HORIZON of midnight
+    // start that trip at midnight instead, already partway along their route, so the map isn't
+    // empty at the start. Only driving trips and walking trips get warmed up this way; people are
+    // picked by the RNG passed to instantiate.
+    pub warmup: Option<f64>,
+}
+
+// Something that happens once at a fixed time, instead of the usual trips from PersonSpec
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum TimedEvent {
+    // Nobody can start a turn into the lane until it's reopened. Agents already on the lane or in
+    // the middle of a turn into it finish normally.
+    CloseLane {
+        time: Time,
+        lane: LaneID,
+    },
+    ReopenLane {
+        time: Time,
+        lane: LaneID,
+    },
+    // Lots of people all leaving from the same place at once, like a stadium emptying out.
+    // They're spread evenly across the destinations.
+    BurstSpawn {
+        time: Time,
+        num_people: usize,
+        from: TripEndpoint,
+        to: Vec<TripEndpoint>,
+        mode: TripMode,
+        purpose: TripPurpose,
+    },
+}
+
+impl TimedEvent {
+    pub fn time(&self) -> Time {
+        match self {
+            TimedEvent::CloseLane { time, .. }
+            | TimedEvent::ReopenLane { time, .. }
+            | TimedEvent::BurstSpawn { time, .. } => *time,
+        }
+    }
+}
+
+// How to place the cars that people own when the simulation starts
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct SeedParkedCars {
+    // The chance of passing up a free spot near the building and continuing to search farther
+    // away.
+    pub skip_probability: f64,
+    // Only look for spots this many roads away from the building. None means keep searching
+    // everything reachable.
+    pub max_search_radius_roads: Option<usize>,
+    // Instantiating warns loudly if more than this fraction of cars can't be placed.
+    pub max_failure_fraction: f64,
+    // Cars that start parked at a building are picked from these, by weight. If this is empty,
+    // they're normal cars.
+    pub vehicles: Vec<ParkedVehicleSpec>,
+    // The chance of a car using its building's own off-street parking, instead of a public spot.
+    // Cars fall back to public spots when the building is full. None means always try the
+    // building first.
+    pub percent_offstreet: Option<f64>,
+    // The fraction of all seeded parked cars that belong to nobody, like visitors. Nobody ever
+    // drives them away; they just take up spots. Must be less than 1. Only used with
+    // ParkedCarCount::FromTrips.
+    pub percent_unowned: f64,
+    pub count: ParkedCarCount,
+}
+
+// How many parked cars to seed in total
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum ParkedCarCount {
+    // Just the cars people need for their trips, plus percent_unowned
+    FromTrips,
+    // Fill up this fraction of all on-street and parking lot spots. People still get the cars
+    // their trips need; unowned cars make up the rest. This can't ask for more cars than there
+    // are spots.
+    TargetOccupancy(f64),
+}
+
+impl Default for ParkedCarCount {
+    fn default() -> ParkedCarCount {
+        ParkedCarCount::FromTrips
+    }
+}
+
+impl Default for SeedParkedCars {
+    fn default() -> SeedParkedCars {
+        SeedParkedCars {
+            skip_probability: 0.0,
+            max_search_radius_roads: None,
+            max_failure_fraction: 1.0,
+            vehicles: Vec::new(),
+            percent_offstreet: None,
+            percent_unowned: 0.0,
+            count: ParkedCarCount::FromTrips,
+        }
+    }
+}
+
+impl SeedParkedCars {
+    // Fill this fraction of on-street and lot spots, like "80% of street parking is taken"
+    pub fn target_occupancy(pct: f64) -> SeedParkedCars {
+        SeedParkedCars {
+            count: ParkedCarCount::TargetOccupancy(pct),
+            ..Default::default()
+        }
+    }
+
+    fn rand_vehicle(&self, rng: &mut XorShiftRng) -> VehicleSpec {
+        if self.vehicles.is_empty() {
+            return Scenario::rand_car(rng);
+        }
+        let spec = self
+            .vehicles
+            .choose_weighted(rng, |v| v.weight)
+            .expect("seed_parked_cars vehicles need at least one positive weight");
+        let length = if spec.min_length == spec.max_length {
+            spec.min_length
+        } else {
+            Scenario::rand_dist(rng, spec.min_length, spec.max_length)
+        };
+        VehicleSpec {
+            vehicle_type: VehicleType::Car,
+            length,
+            max_speed: spec.max_speed,
+        }
+    }
+}
+
+// Something like a delivery van. These still park and drive like cars.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ParkedVehicleSpec {
+    // The length is uniformly picked from [min_length, max_length]
+    pub min_length: Distance,
+    pub max_length: Distance,
+    pub max_speed: Option<Speed>,
+    pub weight: f64,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -30,6 +181,11 @@ pub struct PersonSpec {
     pub id: PersonID,
     // Just used for debugging
     pub orig_id: Option<OrigPersonID>,
+    // Also just for debugging; what generated this person, like "spawn_over_time 3"
+    pub provenance: Option<String>,
+    // A stable name for this person, like "commuter from Montlake", to find them later with
+    // Sim::get_agent_by_label. Must be unique in the scenario.
+    pub label: Option<String>,
     pub trips: Vec<IndividTrip>,
 }
 
@@ -37,15 +193,19 @@ pub struct PersonSpec {
 pub struct IndividTrip {
     pub depart: Time,
     pub trip: SpawnTrip,
+    pub purpose: TripPurpose,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum SpawnTrip {
-    // Only for interactive / debug trips
+    // Only for interactive / debug trips, and trips warmed up to start partway
     VehicleAppearing {
         start: Position,
         goal: DrivingGoal,
         is_bike: bool,
+        // Kept from a FromBorder trip that was started partway
+        origin: Option<OffMapLocation>,
+        give_up_at: Option<Time>,
     },
     FromBorder {
         dr: DirectedRoadID,
@@ -53,6 +213,8 @@ pub enum SpawnTrip {
         // For bikes starting at a border, use FromBorder. UsingBike implies a walk->bike trip.
         is_bike: bool,
         origin: Option<OffMapLocation>,
+        // If set, this is a metered spawn. See BorderSpawnOverTime.
+        give_up_at: Option<Time>,
     },
     UsingParkedCar(BuildingID, DrivingGoal),
     UsingBike(SidewalkSpot, DrivingGoal),
@@ -73,9 +235,136 @@ pub struct OffMapLocation {
     pub gps: LonLat,
 }
 
+// What instantiating a Scenario actually did. Useful for calibrating against a real modal split.
+#[derive(Clone, Debug, Default)]
+pub struct InstantiateSummary {
+    // Only known when the RNG was made by instantiate_with_seed
+    pub rng_seed: Option<u64>,
+    pub vehicle_appearing: usize,
+    // Border spawns where the vehicle won't fit
+    pub no_room_to_spawn: usize,
+    pub using_parked_car: usize,
+    pub using_bike: usize,
+    pub just_walking: usize,
+    pub using_transit: usize,
+    pub remote: usize,
+    pub parked_cars_seeded: usize,
+    pub parked_cars_not_seeded: usize,
+    pub parked_cars_unowned: usize,
+    // Set when too many owned cars couldn't find a spot, per max_failure_fraction
+    pub parked_cars_error: Option<String>,
+}
+
+// What would go wrong instantiating a Scenario, found without running anything
+#[derive(Clone, Debug, Default)]
+pub struct FeasibilityReport {
+    // From Scenario::validate. If there are any, nothing else is checked.
+    pub problems: Vec<String>,
+    // Border spawns where the vehicle won't fit
+    pub no_room_to_spawn: usize,
+    // The borders causing no_room_to_spawn
+    pub borders_missing_lanes: BTreeSet<DirectedRoadID>,
+    pub parked_cars: usize,
+    pub parked_cars_without_spots: usize,
+}
+
+impl InstantiateSummary {
+    fn add(&mut self, spec: &TripSpec) {
+        match spec {
+            TripSpec::VehicleAppearing { .. } => {
+                self.vehicle_appearing += 1;
+            }
+            TripSpec::NoRoomToSpawn { .. } => {
+                self.no_room_to_spawn += 1;
+            }
+            TripSpec::UsingParkedCar { .. } => {
+                self.using_parked_car += 1;
+            }
+            TripSpec::UsingBike { .. } => {
+                self.using_bike += 1;
+            }
+            TripSpec::JustWalking { .. } => {
+                self.just_walking += 1;
+            }
+            TripSpec::UsingTransit { .. } => {
+                self.using_transit += 1;
+            }
+            TripSpec::Remote { .. } => {
+                self.remote += 1;
+            }
+        }
+    }
+}
+
 impl Scenario {
+    pub fn instantiate(
+        &self,
+        sim: &mut Sim,
+        map: &Map,
+        rng: &mut XorShiftRng,
+        timer: &mut Timer,
+    ) -> InstantiateSummary {
+        self.instantiate_with_progress(sim, map, rng, timer, &mut |_| {})
+    }
+
+    // Like instantiate, but makes the RNG from a seed, and records the seed in the summary. Put
+    // the seed in bug reports to reproduce a run exactly.
+    pub fn instantiate_with_seed(
+        &self,
+        sim: &mut Sim,
+        map: &Map,
+        seed: u64,
+        timer: &mut Timer,
+    ) -> InstantiateSummary {
+        timer.note(format!(
+            "Instantiating {} with RNG seed {}",
+            self.scenario_name, seed
+        ));
+        let mut rng = XorShiftRng::seed_from_u64(seed);
+        let mut summary = self.instantiate(sim, map, &mut rng, timer);
+        summary.rng_seed = Some(seed);
+        summary
+    }
+
+    // Like instantiate, but also calls progress with the fraction done, for drawing a progress
+    // bar. The timer still prints like usual.
+    //
     // Any case where map edits could change the calls to the RNG, we have to fork.
-    pub fn instantiate(&self, sim: &mut Sim, map: &Map, rng: &mut XorShiftRng, timer: &mut Timer) {
+    pub fn instantiate_with_progress(
+        &self,
+        sim: &mut Sim,
+        map: &Map,
+        rng: &mut XorShiftRng,
+        timer: &mut Timer,
+        progress: &mut dyn FnMut(f64),
+    ) -> InstantiateSummary {
+        if let Err(problems) = self.validate_onto(sim, map) {
+            panic!(
+                "{} has {} problems:\n{}",
+                self.scenario_name,
+                problems.len(),
+                problems.join("\n")
+            );
+        }
+
+        if let Some(fraction) = self.warmup {
+            let mut warm = self.clone();
+            warm.warmup = None;
+            warm.apply_warmup(fraction, map, rng, timer);
+            return warm.instantiate_with_progress(sim, map, rng, timer, progress);
+        }
+
+        // The people from a BurstSpawn are just like everybody else once they exist
+        if self
+            .events
+            .iter()
+            .any(|ev| matches!(ev, TimedEvent::BurstSpawn { .. }))
+        {
+            let mut expanded = self.clone();
+            expanded.expand_burst_spawns(map);
+            return expanded.instantiate_with_progress(sim, map, rng, timer, progress);
+        }
+
         sim.set_name(self.scenario_name.clone());
 
         timer.start(format!("Instantiating {}", self.scenario_name));
@@ -93,47 +382,193 @@ impl Scenario {
             }
         }
 
+        let mut summary = InstantiateSummary::default();
+        let mut streams = PersonStreams::new(rng);
         timer.start_iter("trips for People", self.people.len());
-        let mut spawner = sim.make_spawner();
         let mut parked_cars: Vec<(Vehicle, BuildingID)> = Vec::new();
-        for p in &self.people {
+        // Everything touching the main RNG happens serially, so the results don't depend on
+        // threads. (person index, trip index, trip, vehicle, RNG for that trip)
+        let mut requests: Vec<(usize, usize, SpawnTrip, Option<Vehicle>, XorShiftRng)> = Vec::new();
+        // Creating people is the first half of the progress, scheduling their trips the second
+        let num_people = self.people.len().max(1) as f64;
+        for (person_idx, p) in self.people.iter().enumerate() {
             timer.next();
+            progress(0.5 * (person_idx as f64) / num_people);
 
-            if let Err(err) = p.check_schedule(map) {
-                panic!("{}", err);
-            }
-
+            let streams = streams.get(p);
             let (vehicle_specs, cars_initially_parked_at, vehicle_foreach_trip) =
-                p.get_vehicles(rng);
+                p.get_vehicles(&self.seed_parked_cars, &mut streams.vehicles);
             sim.new_person(
                 p.id,
                 p.orig_id,
-                Scenario::rand_ped_speed(rng),
+                p.provenance.clone(),
+                Scenario::rand_ped_speed(&mut streams.ped_speeds),
                 vehicle_specs,
             );
+            if let Some(ref label) = p.label {
+                // validate_onto already caught this, so it's not worth stopping over
+                if let Err(err) = sim.set_person_label(p.id, label.clone()) {
+                    timer.warn(err);
+                }
+            }
             let person = sim.get_person(p.id);
             for (idx, b) in cars_initially_parked_at {
                 parked_cars.push((person.vehicles[idx].clone(), b));
             }
-            for (t, maybe_idx) in p.trips.iter().zip(vehicle_foreach_trip) {
+            for (trip_idx, (t, maybe_idx)) in p.trips.iter().zip(vehicle_foreach_trip).enumerate() {
                 // The RNG call might change over edits for picking the spawning lane from a border
                 // with multiple choices for a vehicle type.
-                let mut tmp_rng = abstutil::fork_rng(rng);
-                let spec = t.trip.clone().to_trip_spec(
-                    maybe_idx.map(|idx| person.vehicles[idx].id),
-                    &mut tmp_rng,
-                    map,
-                );
-                spawner.schedule_trip(person, t.depart, spec, t.trip.start(map), map);
+                let tmp_rng = abstutil::fork_rng(&mut streams.trips);
+                requests.push((
+                    person_idx,
+                    trip_idx,
+                    t.trip.clone(),
+                    maybe_idx.map(|idx| person.vehicles[idx].clone()),
+                    tmp_rng,
+                ));
+            }
+        }
+
+        let specs = timer.parallelize(
+            "turn trips into specs",
+            requests,
+            |(person_idx, trip_idx, trip, vehicle, mut tmp_rng)| {
+                let (spec, warning) = trip.to_trip_spec(vehicle.as_ref(), &mut tmp_rng, map);
+                (person_idx, trip_idx, spec, warning)
+            },
+        );
+        // parallelize preserves the order, so trips are scheduled just like before
+        let mut spawner = sim.make_spawner();
+        let num_specs = specs.len().max(1) as f64;
+        for (idx, (person_idx, trip_idx, spec, warning)) in specs.into_iter().enumerate() {
+            progress(0.5 + 0.5 * (idx as f64) / num_specs);
+            if let Some(warning) = warning {
+                timer.warn(warning);
             }
+            let p = &self.people[person_idx];
+            let t = &p.trips[trip_idx];
+            summary.add(&spec);
+            spawner.schedule_trip(
+                sim.get_person(p.id),
+                t.depart,
+                spec,
+                t.trip.start(map),
+                t.purpose,
+                map,
+            );
         }
 
         // parked_cars is stable over map edits, so don't fork.
         parked_cars.shuffle(rng);
-        seed_parked_cars(parked_cars, sim, map, rng, timer);
+        let num_parked_cars = parked_cars.len();
+        match try_seed_parked_cars(parked_cars, &self.seed_parked_cars, sim, map, rng, timer) {
+            Ok(seeded) => {
+                summary.parked_cars_seeded = seeded;
+            }
+            Err((seeded, err)) => {
+                timer.warn(err.clone());
+                summary.parked_cars_seeded = seeded;
+                summary.parked_cars_error = Some(err);
+            }
+        }
+
+        summary.parked_cars_not_seeded = num_parked_cars - summary.parked_cars_seeded;
+        summary.parked_cars_unowned = seed_unowned_parked_cars(
+            summary.parked_cars_seeded,
+            &self.seed_parked_cars,
+            sim,
+            rng,
+            timer,
+        );
 
         sim.flush_spawner(spawner, map, timer);
+        for ev in &self.events {
+            match ev {
+                TimedEvent::CloseLane { time, lane } => {
+                    sim.schedule_lane_closure(*time, *lane, true);
+                }
+                TimedEvent::ReopenLane { time, lane } => {
+                    sim.schedule_lane_closure(*time, *lane, false);
+                }
+                TimedEvent::BurstSpawn { .. } => unreachable!(),
+            }
+        }
+        progress(1.0);
         timer.stop(format!("Instantiating {}", self.scenario_name));
+        summary
+    }
+
+    // Like instantiate, but just reports what would go wrong, without touching a Sim. The RNG
+    // isn't used exactly like instantiate does, so the parking results are an estimate.
+    pub fn feasibility_check(
+        &self,
+        map: &Map,
+        rng: &mut XorShiftRng,
+        timer: &mut Timer,
+    ) -> FeasibilityReport {
+        let mut report = FeasibilityReport::default();
+        if let Err(problems) = self.validate(map) {
+            report.problems = problems;
+            return report;
+        }
+        let mut streams = PersonStreams::new(rng);
+
+        let mut parked_at: Vec<BuildingID> = Vec::new();
+        timer.start_iter("check trips for People", self.people.len());
+        for p in &self.people {
+            timer.next();
+            let (vehicle_specs, cars_initially_parked_at, vehicle_foreach_trip) =
+                p.get_vehicles(&self.seed_parked_cars, &mut streams.get(p).vehicles);
+            parked_at.extend(cars_initially_parked_at.into_iter().map(|(_, b)| b));
+            for (t, maybe_idx) in p.trips.iter().zip(vehicle_foreach_trip) {
+                if let SpawnTrip::FromBorder { dr, is_bike, .. } = t.trip {
+                    let vehicle_len = vehicle_specs[maybe_idx.unwrap()].length;
+                    let constraints = if is_bike {
+                        PathConstraints::Bike
+                    } else {
+                        PathConstraints::Car
+                    };
+                    if dr.lanes(constraints, map).into_iter().all(|l| {
+                        TripSpec::spawn_vehicle_at(
+                            Position::new(l, Distance::ZERO),
+                            vehicle_len,
+                            map,
+                        )
+                        .is_none()
+                    }) {
+                        report.no_room_to_spawn += 1;
+                        report.borders_missing_lanes.insert(dr);
+                    }
+                }
+            }
+        }
+
+        report.parked_cars = parked_at.len();
+        parked_at.shuffle(rng);
+        let parking = ParkingSimState::new(map, timer);
+        let (mut open_spots_per_road, mut skip_rng) =
+            group_open_spots(parking.get_all_parking_spots().1, map, rng);
+        for (idx, b) in parked_at.into_iter().enumerate() {
+            if pick_spot(
+                b,
+                &mut open_spots_per_road,
+                &self.seed_parked_cars,
+                &mut skip_rng,
+                map,
+                timer,
+            )
+            .is_none()
+            {
+                report.parked_cars_without_spots += 1;
+                // Same as seed_parked_cars; everything reachable is full
+                if self.seed_parked_cars.max_search_radius_roads.is_none() {
+                    report.parked_cars_without_spots += report.parked_cars - idx - 1;
+                    break;
+                }
+            }
+        }
+
+        report
     }
 
     pub fn save(&self) {
@@ -149,7 +584,187 @@ impl Scenario {
             map_name: map.get_name().to_string(),
             people: Vec::new(),
             only_seed_buses: Some(BTreeSet::new()),
+            seed_parked_cars: SeedParkedCars::default(),
+            events: Vec::new(),
+            warmup: None,
+        }
+    }
+
+    fn apply_warmup(&mut self, fraction: f64, map: &Map, rng: &mut XorShiftRng, timer: &mut Timer) {
+        // Only touch the main RNG once, so turning this on doesn't change everything else
+        let mut warmup_rng = abstutil::fork_rng(rng);
+        let mut requests = Vec::new();
+        for (idx, person) in self.people.iter().enumerate() {
+            if person.trips.is_empty()
+                || person.trips[0].depart > Time::START_OF_DAY + WARMUP_HORIZON
+            {
+                continue;
+            }
+            if warmup_rng.gen_bool(fraction) {
+                requests.push((
+                    idx,
+                    person.trips[0].trip.clone(),
+                    abstutil::fork_rng(&mut warmup_rng),
+                ));
+            }
+        }
+        let results = timer.parallelize(
+            "start trips partway",
+            requests,
+            |(idx, trip, mut tmp_rng)| (idx, trip.start_partway(&mut tmp_rng, map)),
+        );
+        let mut warmed = 0;
+        for (idx, maybe_trip) in results {
+            if let Some(trip) = maybe_trip {
+                let first = &mut self.people[idx].trips[0];
+                first.depart = Time::START_OF_DAY;
+                first.trip = trip;
+                warmed += 1;
+            }
         }
+        timer.note(format!(
+            "{} people start partway through their first trip",
+            prettyprint_usize(warmed)
+        ));
+    }
+
+    // Turns each BurstSpawn into new people, numbered after everybody else
+    fn expand_burst_spawns(&mut self, map: &Map) {
+        for ev in std::mem::replace(&mut self.events, Vec::new()) {
+            if let TimedEvent::BurstSpawn {
+                time,
+                num_people,
+                from,
+                to,
+                mode,
+                purpose,
+            } = ev
+            {
+                for idx in 0..num_people {
+                    let id = PersonID(self.people.len());
+                    self.people.push(PersonSpec {
+                        id,
+                        orig_id: None,
+                        provenance: Some(format!("burst spawn at {}", time)),
+                        label: None,
+                        trips: vec![IndividTrip {
+                            depart: time,
+                            trip: SpawnTrip::new(
+                                from.clone(),
+                                to[idx % to.len()].clone(),
+                                mode,
+                                map,
+                            ),
+                            purpose,
+                        }],
+                    });
+                }
+            } else {
+                self.events.push(ev);
+            }
+        }
+    }
+
+    // Adds transit riders from observed boarding counts. For each (stop, count, time of day), that
+    // many people leave a building next to the stop a few minutes before the time, board some
+    // route serving the stop, and ride to another stop on the route with buildings near it.
+    // Returns how many people were added, or fails without changing anything if some stop
+    // doesn't exist or isn't served by any route.
+    pub fn add_transit_demand_from_boardings(
+        &mut self,
+        stop_boardings: Vec<(BusStopID, usize, Duration)>,
+        map: &Map,
+        rng: &mut XorShiftRng,
+    ) -> Result<usize, String> {
+        let mut problems = Vec::new();
+        for (stop, _, _) in &stop_boardings {
+            if map.maybe_get_bs(*stop).is_none() {
+                problems.push(format!("{} doesn't exist", stop));
+            } else if map.get_routes_serving_stop(*stop).is_empty() {
+                problems.push(format!("No routes serve {}", stop));
+            }
+        }
+        if !problems.is_empty() {
+            return Err(problems.join("\n"));
+        }
+
+        let num_before = self.people.len();
+        for (stop1, count, time) in stop_boardings {
+            let start_pos = map.get_bs(stop1).sidewalk_pos;
+            let routes = map.get_routes_serving_stop(stop1);
+            for _ in 0..count {
+                let route = routes.choose(rng).unwrap();
+                let destinations: Vec<(BusStopID, BuildingID)> = route
+                    .stops
+                    .iter()
+                    .filter(|s| **s != stop1)
+                    .flat_map(|s| {
+                        map.get_l(map.get_bs(*s).sidewalk_pos.lane())
+                            .building_paths
+                            .iter()
+                            .map(move |b| (*s, *b))
+                    })
+                    .collect();
+                let (stop2, goal) = match destinations.choose(rng) {
+                    Some(pair) => *pair,
+                    None => continue,
+                };
+                let start = match map.get_l(start_pos.lane()).building_paths.choose(rng) {
+                    Some(b) => SidewalkSpot::building(*b, map),
+                    None => {
+                        SidewalkSpot::suddenly_appear(start_pos.lane(), start_pos.dist_along(), map)
+                    }
+                };
+                let leave_early = Duration::seconds(rng.gen_range(0.0, 300.0));
+                let id = PersonID(self.people.len());
+                self.people.push(PersonSpec {
+                    id,
+                    orig_id: None,
+                    provenance: Some(format!("boardings at {}", stop1)),
+                    label: None,
+                    trips: vec![IndividTrip {
+                        depart: Time::START_OF_DAY + (time - leave_early).max(Duration::ZERO),
+                        trip: SpawnTrip::UsingTransit(
+                            start,
+                            SidewalkSpot::building(goal, map),
+                            route.id,
+                            stop1,
+                            stop2,
+                        ),
+                        purpose: TripPurpose::Unknown,
+                    }],
+                });
+            }
+        }
+        Ok(self.people.len() - num_before)
+    }
+
+    // Appends everybody from the other scenario, renumbering them. Buses seeded by either
+    // scenario are kept, and so are the events of both. How to seed parked cars comes from this
+    // scenario.
+    pub fn merge(&mut self, other: &Scenario) -> Result<(), String> {
+        if self.map_name != other.map_name {
+            return Err(format!(
+                "Can't merge {} on {} with {} on {}",
+                self.scenario_name, self.map_name, other.scenario_name, other.map_name
+            ));
+        }
+
+        self.scenario_name = format!("{} + {}", self.scenario_name, other.scenario_name);
+        self.events.extend(other.events.iter().cloned());
+        for person in &other.people {
+            let mut person = person.clone();
+            person.id = PersonID(self.people.len());
+            self.people.push(person);
+        }
+        self.only_seed_buses = match (self.only_seed_buses.take(), &other.only_seed_buses) {
+            (Some(mut routes), Some(more)) => {
+                routes.extend(more.iter().cloned());
+                Some(routes)
+            }
+            _ => None,
+        };
+        Ok(())
     }
 
     pub fn rand_car(rng: &mut XorShiftRng) -> VehicleSpec {
@@ -162,11 +777,7 @@ impl Scenario {
     }
 
     pub fn rand_bike(rng: &mut XorShiftRng) -> VehicleSpec {
-        let max_speed = Some(Scenario::rand_speed(
-            rng,
-            Speed::miles_per_hour(8.0),
-            Speed::miles_per_hour(10.0),
-        ));
+        let max_speed = Some(Scenario::rand_speed(rng, MIN_BIKE_SPEED, MAX_BIKE_SPEED));
         VehicleSpec {
             vehicle_type: VehicleType::Bike,
             length: BIKE_LENGTH,
@@ -209,6 +820,7 @@ impl Scenario {
                     trips.push(IndividTrip {
                         depart: trip.depart + offset,
                         trip: trip.trip.clone(),
+                        purpose: trip.purpose,
                     });
                 }
                 offset += Duration::hours(24);
@@ -223,7 +835,7 @@ impl Scenario {
         // Pass in a dummy RNG
         let mut rng = XorShiftRng::from_seed([0; 16]);
         for p in &self.people {
-            let (_, cars_initially_parked_at, _) = p.get_vehicles(&mut rng);
+            let (_, cars_initially_parked_at, _) = p.get_vehicles(&self.seed_parked_cars, &mut rng);
             for (_, b) in cars_initially_parked_at {
                 per_bldg.inc(b);
             }
@@ -252,18 +864,308 @@ impl Scenario {
         }
         self
     }
+
+    // Checks everything that instantiate relies on, returning all of the problems found instead of
+    // crashing on the first. Useful for scenarios that were edited by hand or made against an
+    // older version of the map.
+    pub fn validate(&self, map: &Map) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        for (name, pct) in vec![
+            ("skip_probability", self.seed_parked_cars.skip_probability),
+            (
+                "max_failure_fraction",
+                self.seed_parked_cars.max_failure_fraction,
+            ),
+            (
+                "percent_offstreet",
+                self.seed_parked_cars.percent_offstreet.unwrap_or(0.0),
+            ),
+        ] {
+            if pct < 0.0 || pct > 1.0 {
+                problems.push(format!("seed_parked_cars {} {} isn't in [0, 1]", name, pct));
+            }
+        }
+        let pct = self.seed_parked_cars.percent_unowned;
+        if pct < 0.0 || pct >= 1.0 {
+            problems.push(format!(
+                "seed_parked_cars percent_unowned {} isn't in [0, 1)",
+                pct
+            ));
+        }
+        if let ParkedCarCount::TargetOccupancy(pct) = self.seed_parked_cars.count {
+            if pct < 0.0 || pct > 1.0 {
+                problems.push(format!(
+                    "seed_parked_cars target occupancy {} isn't in [0, 1]",
+                    pct
+                ));
+            }
+        }
+        for spec in &self.seed_parked_cars.vehicles {
+            if spec.min_length <= Distance::ZERO || spec.min_length > spec.max_length {
+                problems.push(format!("seed_parked_cars has a bad vehicle: {:?}", spec));
+            }
+        }
+        if !self.seed_parked_cars.vehicles.is_empty()
+            && !self
+                .seed_parked_cars
+                .vehicles
+                .iter()
+                .any(|v| v.weight > 0.0)
+        {
+            problems
+                .push("seed_parked_cars vehicles need at least one positive weight".to_string());
+        }
+        if let Some(pct) = self.warmup {
+            if pct < 0.0 || pct > 1.0 {
+                problems.push(format!("warmup {} isn't in [0, 1]", pct));
+            }
+        }
+        for ev in &self.events {
+            match ev {
+                TimedEvent::CloseLane { lane, .. } | TimedEvent::ReopenLane { lane, .. } => {
+                    if map.maybe_get_l(*lane).is_none() {
+                        problems.push(format!("{:?} has a lane that doesn't exist", ev));
+                    }
+                }
+                TimedEvent::BurstSpawn { from, to, mode, .. } => {
+                    if to.is_empty() {
+                        problems.push(format!("{:?} has no destinations", ev));
+                    }
+                    if let Err(err) = from.check(*mode, true, map) {
+                        problems.push(format!("{:?} has a bad origin: {}", ev, err));
+                    }
+                    for endpoint in to {
+                        if let Err(err) = endpoint.check(*mode, false, map) {
+                            problems.push(format!("{:?} has a bad destination: {}", ev, err));
+                        }
+                    }
+                }
+            }
+        }
+        let mut labels: BTreeMap<&str, PersonID> = BTreeMap::new();
+        // People don't have to start from 0, when they're added to a Sim that already has some,
+        // but they do have to be numbered in order.
+        let first_id = self.people.get(0).map(|p| p.id.0).unwrap_or(0);
+        for (idx, person) in self.people.iter().enumerate() {
+            if person.id != PersonID(first_id + idx) {
+                problems.push(format!(
+                    "{} is at index {}, but the first person is {}",
+                    person.id,
+                    idx,
+                    PersonID(first_id)
+                ));
+            }
+            if let Some(ref label) = person.label {
+                if let Some(other) = labels.insert(label, person.id) {
+                    problems.push(format!(
+                        "{} and {} both have the label {}",
+                        other, person.id, label
+                    ));
+                }
+            }
+            let num_problems = problems.len();
+            for trip in &person.trips {
+                if let Err(err) = trip.trip.validate(map) {
+                    problems.push(format!("{} at {}: {}", person.id, trip.depart, err));
+                }
+            }
+            // check_schedule assumes the IDs in each trip are valid
+            if problems.len() == num_problems {
+                if let Err(err) = person.check_schedule(map) {
+                    problems.push(err);
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    // Like validate, but also checks the people can be added to this Sim.
+    pub fn validate_onto(&self, sim: &Sim, map: &Map) -> Result<(), Vec<String>> {
+        let mut problems = match self.validate(map) {
+            Ok(()) => Vec::new(),
+            Err(problems) => problems,
+        };
+        let num_people = sim.get_all_people().len();
+        if let Some(p) = self.people.get(0) {
+            if p.id != PersonID(num_people) {
+                problems.push(format!(
+                    "The first person is {}, but the sim already has {} people",
+                    p.id, num_people
+                ));
+            }
+        }
+        for p in &self.people {
+            if let Some(ref label) = p.label {
+                if let Some(other) = sim.get_person_by_label(label) {
+                    problems.push(format!(
+                        "{} has the label {}, but {} already has it in the sim",
+                        p.id, label, other
+                    ));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+}
+
+// The RngStreams for each block people came from, made as they're needed
+struct PersonStreams {
+    seed: u64,
+    per_block: BTreeMap<Option<ScenarioBlock>, RngStreams>,
+}
+
+impl PersonStreams {
+    fn new(rng: &mut XorShiftRng) -> PersonStreams {
+        PersonStreams {
+            seed: rng.gen(),
+            per_block: BTreeMap::new(),
+        }
+    }
+
+    fn get(&mut self, p: &PersonSpec) -> &mut RngStreams {
+        let block = p
+            .provenance
+            .as_ref()
+            .and_then(|x| ScenarioBlock::from_provenance(x));
+        let seed = self.seed;
+        self.per_block
+            .entry(block)
+            .or_insert_with(|| RngStreams::for_block(seed, block))
+    }
 }
 
-fn seed_parked_cars(
+// After the owned cars are placed, fill random free on-street and lot spots with unowned cars, to
+// reach the amount opts.count asks for. Returns how many were seeded.
+fn seed_unowned_parked_cars(
+    num_owned: usize,
+    opts: &SeedParkedCars,
+    sim: &mut Sim,
+    base_rng: &mut XorShiftRng,
+    timer: &mut Timer,
+) -> usize {
+    let (filled, free) = sim.get_all_parking_spots();
+    // Private off-street spots belong to their building's residents
+    let usable = |spot: &ParkingSpot| match spot {
+        ParkingSpot::Offstreet(_, _) => false,
+        ParkingSpot::Onstreet(_, _) | ParkingSpot::Lot(_, _) => true,
+    };
+    let mut spots: Vec<ParkingSpot> = free.into_iter().filter(usable).collect();
+    let num_unowned = match opts.count {
+        ParkedCarCount::FromTrips => {
+            // Don't touch the RNG by default, so existing scenarios don't change
+            if opts.percent_unowned <= 0.0 || num_owned == 0 {
+                return 0;
+            }
+            ((num_owned as f64) * opts.percent_unowned / (1.0 - opts.percent_unowned)).round()
+                as usize
+        }
+        ParkedCarCount::TargetOccupancy(pct) => {
+            let num_filled = filled.into_iter().filter(usable).count();
+            let target = (pct * ((num_filled + spots.len()) as f64)).round() as usize;
+            if num_filled > target {
+                timer.warn(format!(
+                    "People's own cars already fill {} spots, more than the target of {}",
+                    prettyprint_usize(num_filled),
+                    prettyprint_usize(target)
+                ));
+            }
+            if num_filled >= target {
+                return 0;
+            }
+            target - num_filled
+        }
+    };
+    let mut rng = abstutil::fork_rng(base_rng);
+    spots.shuffle(&mut rng);
+    if spots.len() < num_unowned {
+        timer.warn(format!(
+            "Only room for {} of {} unowned parked cars",
+            prettyprint_usize(spots.len()),
+            prettyprint_usize(num_unowned)
+        ));
+    }
+    let mut seeded = 0;
+    for spot in spots.into_iter().take(num_unowned) {
+        let vehicle = opts.rand_vehicle(&mut rng);
+        sim.seed_unowned_parked_car(vehicle, spot);
+        seeded += 1;
+    }
+    seeded
+}
+
+// Fails if too many cars couldn't be placed, per opts.max_failure_fraction. Either way, returns the
+// number of cars seeded.
+fn try_seed_parked_cars(
     parked_cars: Vec<(Vehicle, BuildingID)>,
+    opts: &SeedParkedCars,
     sim: &mut Sim,
     map: &Map,
     base_rng: &mut XorShiftRng,
     timer: &mut Timer,
+) -> Result<usize, (usize, String)> {
+    let (mut open_spots_per_road, mut skip_rng) =
+        group_open_spots(sim.get_all_parking_spots().1, map, base_rng);
+
+    let num_cars = parked_cars.len();
+    timer.start_iter("seed parked cars", num_cars);
+    // If an unlimited search fails, then everything reachable is full; don't keep searching.
+    let mut ok = true;
+    let mut seeded = 0;
+    for (vehicle, b) in parked_cars {
+        timer.next();
+        if !ok {
+            continue;
+        }
+        if let Some(spot) = pick_spot(b, &mut open_spots_per_road, opts, &mut skip_rng, map, timer)
+        {
+            sim.seed_parked_car(vehicle, spot, Some(b));
+            seeded += 1;
+        } else if opts.max_search_radius_roads.is_none() {
+            timer.warn(format!(
+                "Not enough room to seed the remaining {} parked cars",
+                prettyprint_usize(num_cars - seeded)
+            ));
+            ok = false;
+        }
+    }
+
+    let failed = num_cars - seeded;
+    if failed > 0 && (failed as f64) > opts.max_failure_fraction * (num_cars as f64) {
+        return Err((
+            seeded,
+            format!(
+                "Couldn't seed {} of {} parked cars",
+                prettyprint_usize(failed),
+                prettyprint_usize(num_cars)
+            ),
+        ));
+    }
+    Ok(seeded)
+}
+
+// Groups the open spots by road, along with the building they're restricted to. Also returns the
+// RNG to use while searching.
+fn group_open_spots(
+    spots: Vec<ParkingSpot>,
+    map: &Map,
+    base_rng: &mut XorShiftRng,
+) -> (
+    BTreeMap<RoadID, Vec<(ParkingSpot, Option<BuildingID>)>>,
+    XorShiftRng,
 ) {
     let mut open_spots_per_road: BTreeMap<RoadID, Vec<(ParkingSpot, Option<BuildingID>)>> =
         BTreeMap::new();
-    for spot in sim.get_all_parking_spots().1 {
+    for spot in spots {
         let (r, restriction) = match spot {
             ParkingSpot::Onstreet(l, _) => (map.get_l(l).parent, None),
             ParkingSpot::Offstreet(b, _) => (
@@ -295,124 +1197,228 @@ fn seed_parked_cars(
             spots.shuffle(&mut tmp_rng);
         }
     }
+    let skip_rng = abstutil::fork_rng(base_rng);
+    (open_spots_per_road, skip_rng)
+}
 
-    timer.start_iter("seed parked cars", parked_cars.len());
-    let mut ok = true;
-    for (vehicle, b) in parked_cars {
-        timer.next();
-        if !ok {
-            continue;
-        }
-        if let Some(spot) = find_spot_near_building(b, &mut open_spots_per_road, map, timer) {
-            sim.seed_parked_car(vehicle, spot);
-        } else {
-            timer.warn("Not enough room to seed parked cars.".to_string());
-            ok = false;
+fn pick_spot(
+    b: BuildingID,
+    open_spots_per_road: &mut BTreeMap<RoadID, Vec<(ParkingSpot, Option<BuildingID>)>>,
+    opts: &SeedParkedCars,
+    rng: &mut XorShiftRng,
+    map: &Map,
+    timer: &mut Timer,
+) -> Option<ParkingSpot> {
+    let pct = if let Some(pct) = opts.percent_offstreet {
+        pct
+    } else {
+        return find_spot_near_building(b, open_spots_per_road, true, opts, rng, map, timer);
+    };
+    if rng.gen_bool(pct) {
+        // The building's off-street spots are grouped with its road
+        let r = map.get_l(map.get_b(b).sidewalk()).parent;
+        if let Some(spots) = open_spots_per_road.get_mut(&r) {
+            if let Some(idx) = spots
+                .iter()
+                .position(|(_, restriction)| restriction == &Some(b))
+            {
+                return Some(spots.remove(idx).0);
+            }
         }
     }
+    find_spot_near_building(b, open_spots_per_road, false, opts, rng, map, timer)
 }
 
 // Pick a parking spot for this building. If the building's road has a free spot, use it. If not,
 // start BFSing out from the road in a deterministic way until finding a nearby road with an open
-// spot.
+// spot. With skip_probability, pass up the spots on a road (at most once per road) and keep
+// searching farther away. Only uses the building's private spots if allow_private.
 fn find_spot_near_building(
     b: BuildingID,
     open_spots_per_road: &mut BTreeMap<RoadID, Vec<(ParkingSpot, Option<BuildingID>)>>,
+    allow_private: bool,
+    opts: &SeedParkedCars,
+    rng: &mut XorShiftRng,
     map: &Map,
     timer: &mut Timer,
 ) -> Option<ParkingSpot> {
-    let mut roads_queue: VecDeque<RoadID> = VecDeque::new();
+    // Also track how many roads away from the building
+    let mut roads_queue: VecDeque<(RoadID, usize)> = VecDeque::new();
     let mut visited: HashSet<RoadID> = HashSet::new();
+    let mut skipped: HashSet<RoadID> = HashSet::new();
     {
         let start = map.building_to_road(b).id;
-        roads_queue.push_back(start);
+        roads_queue.push_back((start, 0));
         visited.insert(start);
     }
 
-    loop {
-        if roads_queue.is_empty() {
-            timer.warn(format!(
-                "Giving up looking for a free parking spot, searched {} roads of {}: {:?}",
-                visited.len(),
-                open_spots_per_road.len(),
-                visited
-            ));
+    while let Some((r, dist)) = roads_queue.pop_front() {
+        if opts
+            .max_search_radius_roads
+            .map(|max| dist < max)
+            .unwrap_or(true)
+        {
+            for next_r in map.get_next_roads(r).into_iter() {
+                if !visited.contains(&next_r) {
+                    roads_queue.push_back((next_r, dist + 1));
+                    visited.insert(next_r);
+                }
+            }
         }
-        let r = roads_queue.pop_front()?;
+
         if let Some(spots) = open_spots_per_road.get_mut(&r) {
-            // Fill in all private parking first before
-            // TODO With some probability, skip this available spot and park farther away
-            if let Some(idx) = spots
+            // Fill in all private parking first before public spots
+            let maybe_idx = spots
                 .iter()
-                .position(|(_, restriction)| restriction == &Some(b))
-            {
+                .position(|(_, restriction)| allow_private && restriction == &Some(b))
+                .or_else(|| {
+                    spots
+                        .iter()
+                        .position(|(_, restriction)| restriction.is_none())
+                });
+            if let Some(idx) = maybe_idx {
+                // Come back to this road later. If there's nowhere else to look, just take it.
+                if !roads_queue.is_empty()
+                    && !skipped.contains(&r)
+                    && rng.gen_bool(opts.skip_probability)
+                {
+                    skipped.insert(r);
+                    roads_queue.push_back((r, dist));
+                    continue;
+                }
                 return Some(spots.remove(idx).0);
             }
-            if let Some(idx) = spots
-                .iter()
-                .position(|(_, restriction)| restriction.is_none())
-            {
-                return Some(spots.remove(idx).0);
+        }
+    }
+
+    // Limited searches are expected to fail sometimes; the caller reports the total.
+    if opts.max_search_radius_roads.is_none() {
+        timer.warn(format!(
+            "Giving up looking for a free parking spot near {}. Searched all {} reachable roads, \
+             but {} spots remain elsewhere",
+            b,
+            visited.len(),
+            prettyprint_usize(open_spots_per_road.values().map(|spots| spots.len()).sum())
+        ));
+    }
+    None
+}
+
+fn validate_driving_goal(goal: &DrivingGoal, map: &Map) -> Result<(), String> {
+    match goal {
+        DrivingGoal::ParkNear(b) => {
+            if map.maybe_get_b(*b).is_none() {
+                return Err(format!("{} doesn't exist", b));
+            }
+        }
+        DrivingGoal::Border(i, l, _) => {
+            if map.maybe_get_i(*i).map(|i| i.is_border()) != Some(true) {
+                return Err(format!("{} isn't a border", i));
+            }
+            if map.maybe_get_l(*l).is_none() {
+                return Err(format!("{} doesn't exist", l));
             }
         }
+    }
+    Ok(())
+}
 
-        for next_r in map.get_next_roads(r).into_iter() {
-            if !visited.contains(&next_r) {
-                roads_queue.push_back(next_r);
-                visited.insert(next_r);
+fn validate_sidewalk_spot(spot: &SidewalkSpot, map: &Map) -> Result<(), String> {
+    if map.maybe_get_l(spot.sidewalk_pos.lane()).is_none() {
+        return Err(format!("{} doesn't exist", spot.sidewalk_pos.lane()));
+    }
+    match spot.connection {
+        SidewalkPOI::Building(b) => {
+            if map.maybe_get_b(b).is_none() {
+                return Err(format!("{} doesn't exist", b));
+            }
+        }
+        SidewalkPOI::Border(i, _) => {
+            if map.maybe_get_i(i).map(|i| i.is_border()) != Some(true) {
+                return Err(format!("{} isn't a border", i));
             }
         }
+        _ => {}
     }
+    Ok(())
 }
 
 impl SpawnTrip {
+    // Also returns a warning, if something about the trip had to change
     fn to_trip_spec(
         self,
-        use_vehicle: Option<CarID>,
+        use_vehicle: Option<&Vehicle>,
         rng: &mut XorShiftRng,
         map: &Map,
-    ) -> TripSpec {
-        match self {
-            SpawnTrip::VehicleAppearing { start, goal, .. } => TripSpec::VehicleAppearing {
+    ) -> (TripSpec, Option<String>) {
+        let use_vehicle = use_vehicle.map(|v| (v.id, v.length));
+        let mut warning = None;
+        let spec = match self {
+            SpawnTrip::VehicleAppearing {
+                start,
+                goal,
+                origin,
+                give_up_at,
+                ..
+            } => TripSpec::VehicleAppearing {
                 start_pos: start,
                 goal,
-                use_vehicle: use_vehicle.unwrap(),
+                use_vehicle: use_vehicle.unwrap().0,
                 retry_if_no_room: true,
-                origin: None,
+                origin,
+                give_up_at,
             },
             SpawnTrip::FromBorder {
                 dr,
                 goal,
                 is_bike,
                 origin,
+                give_up_at,
             } => {
-                if let Some(start_pos) = dr
-                    .lanes(
-                        if is_bike {
-                            PathConstraints::Bike
-                        } else {
-                            PathConstraints::Car
-                        },
-                        map,
-                    )
-                    .choose(rng)
-                    // TODO We could be more precise and say exactly what vehicle will be used here
-                    .and_then(|l| {
-                        TripSpec::spawn_vehicle_at(Position::new(*l, Distance::ZERO), is_bike, map)
+                let vehicle_len = use_vehicle.unwrap().1;
+                let lanes = dr.lanes(
+                    if is_bike {
+                        PathConstraints::Bike
+                    } else {
+                        PathConstraints::Car
+                    },
+                    map,
+                );
+                // Make sure the entire vehicle fits on the lane, not just its front. Only pick
+                // from the lanes that are long enough.
+                let start_positions: Vec<Position> = lanes
+                    .iter()
+                    .filter_map(|l| {
+                        TripSpec::spawn_vehicle_at(
+                            Position::new(*l, Distance::ZERO),
+                            vehicle_len,
+                            map,
+                        )
                     })
-                {
+                    .collect();
+                let maybe_start_pos = start_positions.choose(rng).cloned();
+                if maybe_start_pos.is_none() && !lanes.is_empty() {
+                    warning = Some(format!(
+                        "No lane from {} is long enough to spawn {:?} of length {}",
+                        dr,
+                        use_vehicle.unwrap().0,
+                        vehicle_len
+                    ));
+                }
+                if let Some(start_pos) = maybe_start_pos {
                     TripSpec::VehicleAppearing {
                         start_pos,
                         goal,
-                        use_vehicle: use_vehicle.unwrap(),
+                        use_vehicle: use_vehicle.unwrap().0,
                         retry_if_no_room: true,
                         origin,
+                        give_up_at,
                     }
                 } else {
                     TripSpec::NoRoomToSpawn {
                         i: dr.src_i(map),
                         goal,
-                        use_vehicle: use_vehicle.unwrap(),
+                        use_vehicle: use_vehicle.unwrap().0,
                         origin,
                     }
                 }
@@ -420,10 +1426,10 @@ impl SpawnTrip {
             SpawnTrip::UsingParkedCar(start_bldg, goal) => TripSpec::UsingParkedCar {
                 start_bldg,
                 goal,
-                car: use_vehicle.unwrap(),
+                car: use_vehicle.unwrap().0,
             },
             SpawnTrip::UsingBike(start, goal) => TripSpec::UsingBike {
-                bike: use_vehicle.unwrap(),
+                bike: use_vehicle.unwrap().0,
                 start,
                 goal,
             },
@@ -446,14 +1452,175 @@ impl SpawnTrip {
                 trip_time,
                 mode,
             },
+        };
+        (spec, warning)
+    }
+
+    fn validate(&self, map: &Map) -> Result<(), String> {
+        match self {
+            SpawnTrip::VehicleAppearing { start, goal, .. } => {
+                if map.maybe_get_l(start.lane()).is_none() {
+                    return Err(format!("{} doesn't exist", start.lane()));
+                }
+                validate_driving_goal(goal, map)
+            }
+            SpawnTrip::FromBorder { dr, goal, .. } => {
+                if map.maybe_get_r(dr.id).is_none() {
+                    return Err(format!("{} doesn't exist", dr.id));
+                }
+                if !map.get_i(dr.src_i(map)).is_border() {
+                    return Err(format!("{} doesn't start at a border", dr));
+                }
+                // Map edits might remove the lanes needed at the border. That's not a problem
+                // with the scenario; to_trip_spec handles it with NoRoomToSpawn.
+                validate_driving_goal(goal, map)
+            }
+            SpawnTrip::UsingParkedCar(b, goal) => {
+                if map.maybe_get_b(*b).is_none() {
+                    return Err(format!("{} doesn't exist", b));
+                }
+                validate_driving_goal(goal, map)
+            }
+            SpawnTrip::UsingBike(start, goal) => {
+                validate_sidewalk_spot(start, map)?;
+                validate_driving_goal(goal, map)
+            }
+            SpawnTrip::JustWalking(start, goal) => {
+                validate_sidewalk_spot(start, map)?;
+                validate_sidewalk_spot(goal, map)
+            }
+            SpawnTrip::UsingTransit(start, goal, route, stop1, stop2) => {
+                validate_sidewalk_spot(start, map)?;
+                validate_sidewalk_spot(goal, map)?;
+                if route.0 >= map.get_all_bus_routes().len() {
+                    return Err(format!("{} doesn't exist", route));
+                }
+                for stop in &[*stop1, *stop2] {
+                    if map.maybe_get_bs(*stop).is_none() {
+                        return Err(format!("{:?} doesn't exist", stop));
+                    }
+                }
+                Ok(())
+            }
+            SpawnTrip::Remote { trip_time, .. } => {
+                if *trip_time <= Duration::ZERO {
+                    return Err(format!("Remote trip has a trip_time of {}", trip_time));
+                }
+                Ok(())
+            }
         }
     }
 
-    pub fn start(&self, map: &Map) -> TripEndpoint {
+    // The same trip, but as if it'd started before the simulation did, with the agent appearing
+    // somewhere along the route. None if the trip can't be started partway or has no route.
+    fn start_partway(&self, rng: &mut XorShiftRng, map: &Map) -> Option<SpawnTrip> {
         match self {
-            SpawnTrip::VehicleAppearing { ref start, .. } => {
-                TripEndpoint::Border(map.get_l(start.lane()).src_i, None)
+            SpawnTrip::JustWalking(ref start, ref goal) => {
+                let path = map.pathfind(PathRequest {
+                    start: start.sidewalk_pos,
+                    end: goal.sidewalk_pos,
+                    constraints: PathConstraints::Pedestrian,
+                })?;
+                let sidewalks: Vec<LaneID> = path
+                    .get_steps()
+                    .iter()
+                    .filter_map(|step| match step {
+                        PathStep::Lane(l) | PathStep::ContraflowLane(l) => Some(*l),
+                        _ => None,
+                    })
+                    .filter(|l| map.get_l(*l).length() > Distance::ZERO)
+                    .collect();
+                let l = *sidewalks.choose(rng)?;
+                let dist = Scenario::rand_dist(rng, Distance::ZERO, map.get_l(l).length());
+                Some(SpawnTrip::JustWalking(
+                    SidewalkSpot::suddenly_appear(l, dist, map),
+                    goal.clone(),
+                ))
+            }
+            SpawnTrip::VehicleAppearing { .. }
+            | SpawnTrip::FromBorder { .. }
+            | SpawnTrip::UsingParkedCar(_, _) => {
+                let (start, goal, is_bike, origin, give_up_at) = match self {
+                    SpawnTrip::VehicleAppearing {
+                        start,
+                        goal,
+                        is_bike,
+                        origin,
+                        give_up_at,
+                    } => (*start, goal, *is_bike, origin.clone(), *give_up_at),
+                    SpawnTrip::FromBorder {
+                        dr,
+                        goal,
+                        is_bike,
+                        origin,
+                        give_up_at,
+                    } => {
+                        let constraints = if *is_bike {
+                            PathConstraints::Bike
+                        } else {
+                            PathConstraints::Car
+                        };
+                        let l = *dr.lanes(constraints, map).get(0)?;
+                        (
+                            Position::new(l, Distance::ZERO),
+                            goal,
+                            *is_bike,
+                            origin.clone(),
+                            *give_up_at,
+                        )
+                    }
+                    SpawnTrip::UsingParkedCar(b, goal) => (
+                        Position::new(map.find_driving_lane_near_building(*b), Distance::ZERO),
+                        goal,
+                        false,
+                        None,
+                        None,
+                    ),
+                    _ => unreachable!(),
+                };
+                let constraints = if is_bike {
+                    PathConstraints::Bike
+                } else {
+                    PathConstraints::Car
+                };
+                let path = map.pathfind(PathRequest {
+                    start,
+                    end: goal.goal_pos(constraints, map),
+                    constraints,
+                })?;
+                // Make sure any vehicle fits behind its starting position
+                let lanes: Vec<LaneID> = path
+                    .get_steps()
+                    .iter()
+                    .filter_map(|step| match step {
+                        PathStep::Lane(l) => Some(*l),
+                        _ => None,
+                    })
+                    .filter(|l| map.get_l(*l).length() > MAX_CAR_LENGTH)
+                    .collect();
+                let l = *lanes.choose(rng)?;
+                let dist = Scenario::rand_dist(rng, MAX_CAR_LENGTH, map.get_l(l).length());
+                Some(SpawnTrip::VehicleAppearing {
+                    start: Position::new(l, dist),
+                    goal: goal.clone(),
+                    is_bike,
+                    origin,
+                    give_up_at,
+                })
             }
+            SpawnTrip::UsingBike(_, _)
+            | SpawnTrip::UsingTransit(_, _, _, _, _)
+            | SpawnTrip::Remote { .. } => None,
+        }
+    }
+
+    pub fn start(&self, map: &Map) -> TripEndpoint {
+        match self {
+            SpawnTrip::VehicleAppearing {
+                ref start,
+                ref origin,
+                ..
+            } => TripEndpoint::Border(map.get_l(start.lane()).src_i, origin.clone()),
             SpawnTrip::FromBorder { dr, ref origin, .. } => {
                 TripEndpoint::Border(dr.src_i(map), origin.clone())
             }
@@ -509,6 +1676,7 @@ impl SpawnTrip {
                     goal: to.driving_goal(PathConstraints::Car, map),
                     is_bike: false,
                     origin: origin.clone(),
+                    give_up_at: None,
                 },
             },
             TripMode::Bike => match from {
@@ -521,6 +1689,7 @@ impl SpawnTrip {
                     goal: to.driving_goal(PathConstraints::Bike, map),
                     is_bike: true,
                     origin: origin.clone(),
+                    give_up_at: None,
                 },
             },
             TripMode::Walk => {
@@ -588,6 +1757,7 @@ impl PersonSpec {
 
     fn get_vehicles(
         &self,
+        seed_parked_cars: &SeedParkedCars,
         rng: &mut XorShiftRng,
     ) -> (
         Vec<VehicleSpec>,
@@ -656,7 +1826,7 @@ impl PersonSpec {
                     } else {
                         // Need a new car, starting at this building
                         let idx = vehicle_specs.len();
-                        vehicle_specs.push(Scenario::rand_car(rng));
+                        vehicle_specs.push(seed_parked_cars.rand_vehicle(rng));
                         cars_initially_parked_at.push((idx, b));
                         idx
                     };
@@ -705,3 +1875,48 @@ impl PersonSpec {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario(name: &str, map_name: &str, num_people: usize) -> Scenario {
+        Scenario {
+            scenario_name: name.to_string(),
+            map_name: map_name.to_string(),
+            people: (0..num_people)
+                .map(|idx| PersonSpec {
+                    id: PersonID(idx),
+                    orig_id: None,
+                    provenance: None,
+                    label: None,
+                    trips: Vec::new(),
+                })
+                .collect(),
+            only_seed_buses: Some(BTreeSet::new()),
+            seed_parked_cars: SeedParkedCars::default(),
+            events: Vec::new(),
+            warmup: None,
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut commute = scenario("commute", "montlake", 3);
+        let mut event = scenario("event", "montlake", 2);
+        event.only_seed_buses = None;
+        commute.merge(&event).unwrap();
+
+        assert_eq!(commute.scenario_name, "commute + event");
+        assert_eq!(commute.people.len(), 5);
+        for (idx, person) in commute.people.iter().enumerate() {
+            assert_eq!(person.id, PersonID(idx));
+        }
+        assert!(commute.only_seed_buses.is_none());
+
+        assert!(commute
+            .merge(&scenario("elsewhere", "downtown", 1))
+            .is_err());
+        assert_eq!(commute.people.len(), 5);
+    }
+}
@@ -1,9 +1,9 @@
 use crate::{
     CarID, Command, DrivingGoal, OffMapLocation, Person, PersonID, Scheduler, SidewalkSpot,
-    TripEndpoint, TripLeg, TripManager, TripMode, VehicleType, BIKE_LENGTH, MAX_CAR_LENGTH,
+    TripEndpoint, TripLeg, TripManager, TripMode, TripPurpose, VehicleType,
 };
 use abstutil::Timer;
-use geom::{Duration, Time, EPSILON_DIST};
+use geom::{Distance, Duration, Time, EPSILON_DIST};
 use map_model::{
     BuildingID, BusRouteID, BusStopID, IntersectionID, Map, PathConstraints, PathRequest, Position,
 };
@@ -20,6 +20,9 @@ pub enum TripSpec {
         use_vehicle: CarID,
         retry_if_no_room: bool,
         origin: Option<OffMapLocation>,
+        // Only retry spawning until this time, and only when the start of the lane is clear. See
+        // BorderSpawnOverTime's metering.
+        give_up_at: Option<Time>,
     },
     // A VehicleAppearing that failed to even pick a start_pos, because of a bug with badly chosen
     // borders.
@@ -62,7 +65,7 @@ pub enum TripSpec {
 
 // This structure is created temporarily by a Scenario or to interactively spawn agents.
 pub struct TripSpawner {
-    trips: Vec<(PersonID, Time, TripSpec, TripEndpoint)>,
+    trips: Vec<(PersonID, Time, TripSpec, TripEndpoint, TripPurpose)>,
 }
 
 impl TripSpawner {
@@ -76,6 +79,7 @@ impl TripSpawner {
         start_time: Time,
         spec: TripSpec,
         trip_start: TripEndpoint,
+        purpose: TripPurpose,
         map: &Map,
     ) {
         // TODO We'll want to repeat this validation when we spawn stuff later for a second leg...
@@ -164,6 +168,7 @@ impl TripSpawner {
                                 goal: SidewalkSpot::building(*b, map),
                             },
                             trip_start,
+                            purpose,
                         ));
                         return;
                     }
@@ -173,7 +178,8 @@ impl TripSpawner {
             TripSpec::Remote { .. } => {}
         };
 
-        self.trips.push((person.id, start_time, spec, trip_start));
+        self.trips
+            .push((person.id, start_time, spec, trip_start, purpose));
     }
 
     pub fn finalize(
@@ -209,7 +215,7 @@ impl TripSpawner {
         }
 
         timer.start_iter("spawn trips", paths.len());
-        for ((p, start_time, spec, trip_start), maybe_req, maybe_path) in paths {
+        for ((p, start_time, spec, trip_start, purpose), maybe_req, maybe_path) in paths {
             timer.next();
 
             // TODO clone() is super weird to do here, but we just need to make the borrow checker
@@ -228,6 +234,7 @@ impl TripSpawner {
                     trips.new_trip(
                         person.id,
                         start_time,
+                        purpose,
                         trip_start,
                         if use_vehicle.1 == VehicleType::Bike {
                             TripMode::Bike
@@ -248,6 +255,7 @@ impl TripSpawner {
                     trips.new_trip(
                         person.id,
                         start_time,
+                        purpose,
                         trip_start,
                         if use_vehicle.1 == VehicleType::Bike {
                             TripMode::Bike
@@ -272,6 +280,7 @@ impl TripSpawner {
                     trips.new_trip(
                         person.id,
                         start_time,
+                        purpose,
                         trip_start,
                         TripMode::Drive,
                         legs,
@@ -281,6 +290,7 @@ impl TripSpawner {
                 TripSpec::JustWalking { goal, .. } => trips.new_trip(
                     person.id,
                     start_time,
+                    purpose,
                     trip_start,
                     TripMode::Walk,
                     vec![TripLeg::Walk(goal.clone())],
@@ -299,7 +309,15 @@ impl TripSpawner {
                         }
                         DrivingGoal::Border(_, _, _) => {}
                     };
-                    trips.new_trip(person.id, start_time, trip_start, TripMode::Bike, legs, map)
+                    trips.new_trip(
+                        person.id,
+                        start_time,
+                        purpose,
+                        trip_start,
+                        TripMode::Bike,
+                        legs,
+                        map,
+                    )
                 }
                 TripSpec::UsingTransit {
                     route,
@@ -312,6 +330,7 @@ impl TripSpawner {
                     trips.new_trip(
                         person.id,
                         start_time,
+                        purpose,
                         trip_start,
                         TripMode::Transit,
                         vec![
@@ -325,6 +344,7 @@ impl TripSpawner {
                 TripSpec::Remote { to, mode, .. } => trips.new_trip(
                     person.id,
                     start_time,
+                    purpose,
                     trip_start,
                     mode,
                     vec![TripLeg::Remote(to)],
@@ -340,10 +360,10 @@ impl TripSpawner {
 }
 
 impl TripSpec {
-    // If possible, fixes problems that schedule_trip would hit.
-    pub fn spawn_vehicle_at(pos: Position, is_bike: bool, map: &Map) -> Option<Position> {
+    // If possible, fixes problems that schedule_trip would hit. The front of the vehicle is
+    // placed at least vehicle_len along the lane, so the rest of it doesn't hang off the start.
+    pub fn spawn_vehicle_at(pos: Position, vehicle_len: Distance, map: &Map) -> Option<Position> {
         let lane_len = map.get_l(pos.lane()).length();
-        let vehicle_len = if is_bike { BIKE_LENGTH } else { MAX_CAR_LENGTH };
         // There's no hope.
         if lane_len <= vehicle_len {
             return None;
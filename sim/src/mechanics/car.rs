@@ -17,6 +17,14 @@ pub struct Car {
     pub trip_and_person: Option<(TripID, PersonID)>,
     pub started_at: Time,
     pub total_blocked_time: Duration,
+    // Scales the speed of everything this car crosses
+    pub speed_multiplier: f64,
+    // If this car was rerouted for being stuck, when that blockage started. Each blockage only
+    // gets one reroute, so a gridlocked car can't flip between two routes forever.
+    pub rerouted_while_blocked_since: Option<Time>,
+    // Map edits made the path ahead stale while this car was in the middle of a turn. It finds a
+    // new way once it reaches the end of the next lane.
+    pub reroute_after_turn: bool,
 
     // In reverse order -- most recently left is first. The sum length of these must be >=
     // vehicle.length.
@@ -48,6 +56,7 @@ impl Car {
         if let Some(s) = self.vehicle.max_speed {
             speed = speed.min(s);
         }
+        speed = speed * self.speed_multiplier;
         let dt = (dist_int.end - dist_int.start) / speed;
         CarState::Crossing(TimeInterval::new(start_time, start_time + dt), dist_int)
     }
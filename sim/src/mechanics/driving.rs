@@ -2,20 +2,16 @@ use crate::mechanics::car::{Car, CarState};
 use crate::mechanics::Queue;
 use crate::{
     ActionAtEnd, AgentID, AgentProperties, CarID, Command, CreateCar, DistanceInterval,
-    DrawCarInput, Event, IntersectionSimState, ParkedCar, ParkingSimState, PersonID, Scheduler,
-    TimeInterval, TransitSimState, TripManager, TripPositions, UnzoomedAgent, Vehicle,
-    WalkingSimState, FOLLOWING_DISTANCE,
+    DrawCarInput, Event, IntersectionSimState, ParkedCar, ParkingSimState, ParkingSpot, PersonID,
+    Scheduler, TimeInterval, TransitSimState, TripManager, TripPositions, UnzoomedAgent, Vehicle,
+    VehicleType, WalkingSimState,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap};
-use geom::{Distance, Duration, PolyLine, Time};
+use geom::{Distance, Duration, PolyLine, Speed, Time};
 use map_model::{LaneID, Map, Path, PathStep, Traversable};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashSet, VecDeque};
 
-const TIME_TO_UNPARK: Duration = Duration::const_seconds(10.0);
-const TIME_TO_PARK: Duration = Duration::const_seconds(15.0);
-const TIME_TO_WAIT_AT_STOP: Duration = Duration::const_seconds(10.0);
-
 // TODO Do something else.
 pub(crate) const BLIND_RETRY_TO_CREEP_FORWARDS: Duration = Duration::const_seconds(0.1);
 pub(crate) const BLIND_RETRY_TO_REACH_END_DIST: Duration = Duration::const_seconds(5.0);
@@ -33,28 +29,63 @@ pub struct DrivingSimState {
     )]
     queues: BTreeMap<Traversable, Queue>,
     events: Vec<Event>,
+    // Missing types move at normal speed
+    speed_multipliers: BTreeMap<VehicleType, f64>,
 
     recalc_lanechanging: bool,
+    config: DrivingConfig,
+    // Cars that were mid-turn during map edits and then had no way to their goal. Sim removes
+    // them after each update.
+    failed_reroutes: Vec<CarID>,
+}
+
+// Tunable parts of the driving model, to see how sensitive results are to them. The defaults are
+// the values that've always been used. There's no timestep or reaction time here: the sim is
+// event-driven, so it has no fixed timestep, and drivers react to whatever is ahead the instant it
+// happens.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DrivingConfig {
+    pub time_to_unpark: Duration,
+    pub time_to_park: Duration,
+    // How long buses wait at each stop
+    pub time_to_wait_at_stop: Duration,
+    // At all speeds (including at rest), cars must be at least this far apart, measured from
+    // front of one car to the back of the other.
+    pub following_distance: Distance,
+}
+
+impl Default for DrivingConfig {
+    fn default() -> DrivingConfig {
+        DrivingConfig {
+            time_to_unpark: Duration::seconds(10.0),
+            time_to_park: Duration::seconds(15.0),
+            time_to_wait_at_stop: Duration::seconds(10.0),
+            following_distance: Distance::meters(1.0),
+        }
+    }
 }
 
 impl DrivingSimState {
-    pub fn new(map: &Map, recalc_lanechanging: bool) -> DrivingSimState {
+    pub fn new(map: &Map, recalc_lanechanging: bool, config: DrivingConfig) -> DrivingSimState {
         let mut sim = DrivingSimState {
             cars: BTreeMap::new(),
             queues: BTreeMap::new(),
             events: Vec::new(),
+            speed_multipliers: BTreeMap::new(),
             recalc_lanechanging,
+            config,
+            failed_reroutes: Vec::new(),
         };
 
         for l in map.all_lanes() {
             if l.lane_type.is_for_moving_vehicles() {
-                let q = Queue::new(Traversable::Lane(l.id), map);
+                let q = Queue::new(Traversable::Lane(l.id), sim.config.following_distance, map);
                 sim.queues.insert(q.id, q);
             }
         }
         for t in map.all_turns().values() {
             if !t.between_sidewalks() {
-                let q = Queue::new(Traversable::Turn(t.id), map);
+                let q = Queue::new(Traversable::Turn(t.id), sim.config.following_distance, map);
                 sim.queues.insert(q.id, q);
             }
         }
@@ -62,6 +93,26 @@ impl DrivingSimState {
         sim
     }
 
+    // Applies to cars of this type already on the map, starting with the next thing they cross,
+    // and to all future ones. 1.0 restores normal speed.
+    pub fn set_speed_multiplier(&mut self, vt: VehicleType, multiplier: f64) {
+        assert!(multiplier > 0.0);
+        if multiplier == 1.0 {
+            self.speed_multipliers.remove(&vt);
+        } else {
+            self.speed_multipliers.insert(vt, multiplier);
+        }
+        for car in self.cars.values_mut() {
+            if car.vehicle.vehicle_type == vt {
+                car.speed_multiplier = multiplier;
+            }
+        }
+    }
+
+    pub fn get_speed_multiplier(&self, vt: VehicleType) -> f64 {
+        self.speed_multipliers.get(&vt).cloned().unwrap_or(1.0)
+    }
+
     // True if it worked
     pub fn start_car_on_lane(
         &mut self,
@@ -77,6 +128,16 @@ impl DrivingSimState {
         if !intersections.nobody_headed_towards(first_lane, map.get_l(first_lane).src_i) {
             return false;
         }
+        if params.give_up_at.is_some()
+            && !self.queues[&Traversable::Lane(first_lane)].is_start_clear(
+                params.vehicle.length,
+                now,
+                &self.cars,
+                &self.queues,
+            )
+        {
+            return false;
+        }
         if let Some(idx) = self.queues[&Traversable::Lane(first_lane)].get_idx_to_insert_car(
             params.start_dist,
             params.vehicle.length,
@@ -84,6 +145,7 @@ impl DrivingSimState {
             &self.cars,
             &self.queues,
         ) {
+            let speed_multiplier = self.get_speed_multiplier(params.vehicle.vehicle_type);
             let mut car = Car {
                 vehicle: params.vehicle,
                 router: params.router,
@@ -93,12 +155,15 @@ impl DrivingSimState {
                 started_at: now,
                 total_blocked_time: Duration::ZERO,
                 trip_and_person: params.trip_and_person,
+                speed_multiplier,
+                rerouted_while_blocked_since: None,
+                reroute_after_turn: false,
             };
             if let Some(p) = params.maybe_parked_car {
                 car.state = CarState::Unparking(
                     params.start_dist,
                     p.spot,
-                    TimeInterval::new(now, now + TIME_TO_UNPARK),
+                    TimeInterval::new(now, now + self.config.time_to_unpark),
                 );
             } else {
                 // Have to do this early
@@ -139,7 +204,7 @@ impl DrivingSimState {
                 queue.cars.insert(idx, car.vehicle.id);
                 // Don't use try_to_reserve_entry -- it's overly conservative.
                 // get_idx_to_insert_car does a more detailed check of the current space usage.
-                queue.reserved_length += car.vehicle.length + FOLLOWING_DISTANCE;
+                queue.reserved_length += car.vehicle.length + self.config.following_distance;
             }
             self.cars.insert(car.vehicle.id, car);
             return true;
@@ -300,7 +365,7 @@ impl DrivingSimState {
                                 follower.total_blocked_time += now - blocked_since;
                                 follower.state = follower.crossing_state(
                                     // Since the follower was Queued, this must be where they are.
-                                    dist - car.vehicle.length - FOLLOWING_DISTANCE,
+                                    dist - car.vehicle.length - self.config.following_distance,
                                     now,
                                     map,
                                 );
@@ -323,6 +388,14 @@ impl DrivingSimState {
             }
             CarState::Queued { .. } => unreachable!(),
             CarState::WaitingToAdvance { blocked_since } => {
+                if car.reroute_after_turn {
+                    car.reroute_after_turn = false;
+                    if !car.router.reroute_after_edits(&car.vehicle, map) {
+                        self.failed_reroutes.push(car.vehicle.id);
+                        return false;
+                    }
+                }
+
                 // 'car' is the leader.
                 let from = car.router.head();
                 let goto = car.router.next();
@@ -333,6 +406,7 @@ impl DrivingSimState {
                     if let Some(s) = car.vehicle.max_speed {
                         speed = speed.min(s);
                     }
+                    speed = speed * car.speed_multiplier;
                     if !intersections.maybe_start_turn(
                         AgentID::Car(car.vehicle.id),
                         t,
@@ -382,7 +456,7 @@ impl DrivingSimState {
                     car.crossing_state_with_end_dist(
                         DistanceInterval::new_driving(
                             Distance::ZERO,
-                            car.vehicle.length + FOLLOWING_DISTANCE,
+                            car.vehicle.length + self.config.following_distance,
                         ),
                         now,
                         map,
@@ -464,7 +538,7 @@ impl DrivingSimState {
                         car.state = CarState::Parking(
                             our_dist,
                             spot,
-                            TimeInterval::new(now, now + TIME_TO_PARK),
+                            TimeInterval::new(now, now + self.config.time_to_park),
                         );
                         // If we don't do this, then we might have another car creep up
                         // behind, see the spot free, and start parking too. This can
@@ -505,7 +579,7 @@ impl DrivingSimState {
                         car.total_blocked_time += now - blocked_since;
                         car.state = CarState::Idling(
                             our_dist,
-                            TimeInterval::new(now, now + TIME_TO_WAIT_AT_STOP),
+                            TimeInterval::new(now, now + self.config.time_to_wait_at_stop),
                         );
                         scheduler
                             .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
@@ -578,7 +652,7 @@ impl DrivingSimState {
         // Hacks to delete cars that're mid-turn
         if let Traversable::Turn(_) = car.router.head() {
             let queue = self.queues.get_mut(&car.router.head()).unwrap();
-            queue.reserved_length += car.vehicle.length + FOLLOWING_DISTANCE;
+            queue.reserved_length += car.vehicle.length + self.config.following_distance;
         }
         if let Some(Traversable::Turn(t)) = car.router.maybe_next() {
             intersections.cancel_request(AgentID::Car(c), t);
@@ -687,7 +761,8 @@ impl DrivingSimState {
         };
 
         // Trim off as many of the oldest last_steps as we've made distance.
-        let mut dist_left_to_cleanup = self.cars[&id].vehicle.length + FOLLOWING_DISTANCE;
+        let mut dist_left_to_cleanup =
+            self.cars[&id].vehicle.length + self.config.following_distance;
         dist_left_to_cleanup -= dist_along_last;
         let mut num_to_trim = None;
         for (idx, step) in self.cars[&id].last_steps.iter().enumerate() {
@@ -713,7 +788,7 @@ impl DrivingSimState {
                     // fine for correctness.
                     DistanceInterval::new_driving(
                         dist_along_last,
-                        self.cars[&id].vehicle.length + FOLLOWING_DISTANCE,
+                        self.cars[&id].vehicle.length + self.config.following_distance,
                     ),
                     now,
                     map,
@@ -829,6 +904,43 @@ impl DrivingSimState {
         result
     }
 
+    // Cars only change speed between lanes and turns, so this is the average speed over whatever
+    // they're crossing now.
+    pub fn current_speed(&self, id: CarID) -> Option<Speed> {
+        let car = self.cars.get(&id)?;
+        if let CarState::Crossing(ref time_int, ref dist_int) = car.state {
+            let dt = time_int.end - time_int.start;
+            if dt > Duration::ZERO {
+                return Some(Speed::meters_per_second(
+                    (dist_int.end - dist_int.start).inner_meters() / dt.inner_seconds(),
+                ));
+            }
+        }
+        Some(Speed::ZERO)
+    }
+
+    // Over every car on the road, including ones stuck in traffic. None if there aren't any.
+    pub fn mean_speed(&self) -> Option<Speed> {
+        if self.cars.is_empty() {
+            return None;
+        }
+        let total: f64 = self
+            .cars
+            .keys()
+            .map(|id| self.current_speed(*id).unwrap().inner_meters_per_second())
+            .sum();
+        Some(Speed::meters_per_second(total / (self.cars.len() as f64)))
+    }
+
+    pub fn num_cars(&self) -> usize {
+        self.cars.len()
+    }
+
+    // Sorted by ID
+    pub fn all_cars(&self) -> Vec<CarID> {
+        self.cars.keys().cloned().collect()
+    }
+
     pub fn does_car_exist(&self, id: CarID) -> bool {
         self.cars.contains_key(&id)
     }
@@ -909,6 +1021,150 @@ impl DrivingSimState {
         }
     }
 
+    // Cars at the front of a queue that've been waiting to turn for at least max_wait. Buses
+    // aren't included.
+    pub fn find_stuck_cars(&self, now: Time, max_wait: Duration) -> Vec<CarID> {
+        self.cars
+            .values()
+            .filter(|car| match car.state {
+                CarState::WaitingToAdvance { blocked_since } => {
+                    car.trip_and_person.is_some() && now - blocked_since >= max_wait
+                }
+                _ => false,
+            })
+            .map(|car| car.vehicle.id)
+            .collect()
+    }
+
+    // After map edits, find cars whose path ahead uses a lane they can't drive on anymore or a turn
+    // that's gone, and send them another way. Cars already on a changed lane finish crossing it.
+    // Returns the cars that couldn't be rerouted.
+    pub fn reroute_after_edits(
+        &mut self,
+        now: Time,
+        map: &Map,
+        scheduler: &mut Scheduler,
+        intersections: &mut IntersectionSimState,
+    ) -> Vec<CarID> {
+        let mut failed = Vec::new();
+        for car in self.cars.values_mut() {
+            // TODO Buses stick to their route
+            if car.vehicle.vehicle_type == VehicleType::Bus {
+                continue;
+            }
+            let constraints = car.vehicle.vehicle_type.to_constraints();
+            let stale = car
+                .router
+                .get_path()
+                .get_steps()
+                .iter()
+                .skip(1)
+                .any(|step| match step {
+                    PathStep::Lane(l) | PathStep::ContraflowLane(l) => {
+                        !constraints.can_use(map.get_l(*l), map)
+                    }
+                    PathStep::Turn(t) => map.maybe_get_t(*t).is_none(),
+                });
+            if !stale {
+                continue;
+            }
+            // Can't pathfind from the middle of a turn, so wait until the car reaches the end of
+            // the next lane
+            if car.router.head().maybe_turn().is_some() {
+                car.reroute_after_turn = true;
+                continue;
+            }
+            let old_next = car.router.maybe_next();
+            if !car.router.reroute_after_edits(&car.vehicle, map) {
+                failed.push(car.vehicle.id);
+                continue;
+            }
+            // If the car was waiting for the old turn, ask for the new one instead
+            if let CarState::WaitingToAdvance { .. } = car.state {
+                if let Some(Traversable::Turn(t)) = old_next {
+                    intersections.cancel_request(AgentID::Car(car.vehicle.id), t);
+                }
+                scheduler.update(now, Command::UpdateCar(car.vehicle.id));
+            }
+        }
+        failed
+    }
+
+    // The cars that were mid-turn during map edits and couldn't find a new way afterwards. The
+    // caller has to remove them.
+    pub fn take_failed_reroutes(&mut self) -> Vec<CarID> {
+        std::mem::replace(&mut self.failed_reroutes, Vec::new())
+    }
+
+    // Tries to send a stuck car a different way. True if it worked. A car that's still stuck after
+    // being rerouted once doesn't get another try.
+    pub fn reroute_stuck_car(
+        &mut self,
+        id: CarID,
+        now: Time,
+        map: &Map,
+        scheduler: &mut Scheduler,
+        intersections: &mut IntersectionSimState,
+    ) -> bool {
+        let car = self.cars.get_mut(&id).unwrap();
+        let blocked_since = match car.state {
+            CarState::WaitingToAdvance { blocked_since } => blocked_since,
+            _ => unreachable!(),
+        };
+        if car.rerouted_while_blocked_since == Some(blocked_since) {
+            return false;
+        }
+        let old_turn = car.router.next().as_turn();
+        if !car.router.reroute(&car.vehicle, map) {
+            return false;
+        }
+        car.rerouted_while_blocked_since = Some(blocked_since);
+        intersections.cancel_request(AgentID::Car(id), old_turn);
+        // Try the new turn immediately
+        scheduler.update(now, Command::UpdateCar(id));
+        true
+    }
+
+    // One line per car, for humans to read
+    pub fn debug_dump(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for car in self.cars.values() {
+            let state = match car.state {
+                CarState::Crossing(ref time_int, ref dist_int) => format!(
+                    "crossing {} to {} at {}",
+                    dist_int.start,
+                    dist_int.end,
+                    Speed::from_dist_time(
+                        dist_int.end - dist_int.start,
+                        time_int.end - time_int.start
+                    )
+                ),
+                CarState::Queued { blocked_since } => format!("queued since {}", blocked_since),
+                CarState::WaitingToAdvance { blocked_since } => format!(
+                    "waiting to advance to {:?} since {}",
+                    car.router.next(),
+                    blocked_since
+                ),
+                CarState::Unparking(dist, ref spot, _) => {
+                    format!("unparking from {:?} at {}", spot, dist)
+                }
+                CarState::Parking(dist, ref spot, _) => {
+                    format!("parking in {:?} at {}", spot, dist)
+                }
+                CarState::Idling(dist, ref time_int) => {
+                    format!("idling at {} until {}", dist, time_int.end)
+                }
+            };
+            lines.push(format!(
+                "{} on {:?}: {}",
+                car.vehicle.id,
+                car.router.head(),
+                state
+            ));
+        }
+        lines
+    }
+
     pub fn debug_lane(&self, id: LaneID) {
         if let Some(ref queue) = self.queues.get(&Traversable::Lane(id)) {
             println!("{}", abstutil::to_json(queue));
@@ -974,6 +1230,15 @@ impl DrivingSimState {
         car.vehicle.owner
     }
 
+    // The spot a car is pulling into or out of right now. True if it's parking.
+    pub fn get_parking_maneuver(&self, id: CarID) -> Option<(ParkingSpot, bool)> {
+        match self.cars.get(&id)?.state {
+            CarState::Parking(_, spot, _) => Some((spot, true)),
+            CarState::Unparking(_, spot, _) => Some((spot, false)),
+            _ => None,
+        }
+    }
+
     // TODO Clean this up
     pub fn find_blockage_front(
         &self,
@@ -1036,6 +1301,93 @@ impl DrivingSimState {
         }
     }
 
+    // Queued behind somebody or waiting to start a turn
+    pub fn count_blocked(&self) -> usize {
+        self.cars
+            .values()
+            .filter(|car| match car.state {
+                CarState::Queued { .. } | CarState::WaitingToAdvance { .. } => true,
+                _ => false,
+            })
+            .count()
+    }
+
+    // Cars blocked for at least threshold, with how long. Longest first.
+    pub fn get_stuck_cars(&self, now: Time, threshold: Duration) -> Vec<(CarID, Duration)> {
+        let mut stuck: Vec<(CarID, Duration)> = self
+            .cars
+            .values()
+            .filter_map(|car| match car.state {
+                CarState::Queued { blocked_since }
+                | CarState::WaitingToAdvance { blocked_since } => {
+                    let dt = now - blocked_since;
+                    if dt >= threshold {
+                        Some((car.vehicle.id, dt))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            })
+            .collect();
+        stuck.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        stuck
+    }
+
+    // Front to back: each car on the lane, where its front is, and how long it's been blocked.
+    pub fn get_lane_queue(&self, now: Time, l: LaneID) -> Vec<(CarID, Distance, Duration)> {
+        let queue = match self.queues.get(&Traversable::Lane(l)) {
+            Some(q) => q,
+            None => {
+                return Vec::new();
+            }
+        };
+        queue
+            .get_car_positions(now, &self.cars, &self.queues)
+            .into_iter()
+            .map(|(id, dist)| {
+                let waiting = match self.cars[&id].state {
+                    CarState::Queued { blocked_since }
+                    | CarState::WaitingToAdvance { blocked_since } => now - blocked_since,
+                    _ => Duration::ZERO,
+                };
+                (id, dist, waiting)
+            })
+            .collect()
+    }
+
+    // The fraction of the lane's length covered by the cars on it. Ignores following distance.
+    pub fn get_lane_occupancy(&self, l: LaneID) -> f64 {
+        self.queues
+            .get(&Traversable::Lane(l))
+            .map(|q| self.queue_occupancy(q))
+            .unwrap_or(0.0)
+    }
+
+    // Only lanes with cars on them
+    pub fn get_all_lane_occupancy(&self) -> BTreeMap<LaneID, f64> {
+        let mut results = BTreeMap::new();
+        for (id, q) in &self.queues {
+            if let Traversable::Lane(l) = id {
+                if !q.cars.is_empty() {
+                    results.insert(*l, self.queue_occupancy(q));
+                }
+            }
+        }
+        results
+    }
+
+    fn queue_occupancy(&self, q: &Queue) -> f64 {
+        if q.geom_len == Distance::ZERO {
+            return 0.0;
+        }
+        let total = q
+            .cars
+            .iter()
+            .fold(Distance::ZERO, |sum, c| sum + self.cars[c].vehicle.length);
+        (total / q.geom_len).min(1.0)
+    }
+
     pub fn collect_events(&mut self) -> Vec<Event> {
         std::mem::replace(&mut self.events, Vec::new())
     }
@@ -4,7 +4,7 @@ use crate::{AgentID, AlertLocation, CarID, Command, Event, Scheduler, Speed, Tri
 use abstutil::{deserialize_btreemap, retain_btreeset, serialize_btreemap};
 use geom::{Duration, Time};
 use map_model::{
-    ControlStopSign, ControlTrafficSignal, IntersectionID, LaneID, Map, RoadID, Traversable,
+    ControlStopSign, ControlTrafficSignal, IntersectionID, LaneID, Map, Phase, RoadID, Traversable,
     TurnID, TurnPriority, TurnType,
 };
 use serde::{Deserialize, Serialize};
@@ -22,6 +22,13 @@ pub struct IntersectionSimState {
     // (x, y) means x is blocked by y. It's a many-to-many relationship. TODO Better data
     // structure.
     blocked_by: BTreeSet<(CarID, CarID)>,
+    // Nobody can start a turn into these
+    closed_lanes: BTreeSet<LaneID>,
+    // How many vehicles finished a turn through each intersection, since the last
+    // reset_throughput
+    throughput: BTreeMap<IntersectionID, usize>,
+    // For debugging, hold some traffic signals in one phase
+    signal_overrides: BTreeMap<IntersectionID, usize>,
     events: Vec<Event>,
 }
 
@@ -57,6 +64,9 @@ impl IntersectionSimState {
             dont_block_the_box,
             break_turn_conflict_cycles,
             blocked_by: BTreeSet::new(),
+            closed_lanes: BTreeSet::new(),
+            throughput: BTreeMap::new(),
+            signal_overrides: BTreeMap::new(),
             events: Vec::new(),
         };
         for i in map.all_intersections() {
@@ -92,6 +102,9 @@ impl IntersectionSimState {
     ) {
         let state = self.state.get_mut(&turn.parent).unwrap();
         assert!(state.accepted.remove(&Request { agent, turn }));
+        if let AgentID::Car(_) = agent {
+            *self.throughput.entry(turn.parent).or_insert(0) += 1;
+        }
         if map.get_t(turn).turn_type != TurnType::SharedSidewalkCorner {
             self.wakeup_waiting(now, turn.parent, scheduler, map);
         }
@@ -129,6 +142,95 @@ impl IntersectionSimState {
         retain_btreeset(&mut self.blocked_by, |(c1, c2)| *c1 != car && *c2 != car);
     }
 
+    // Agents already headed into the lane finish their turn. Everyone else waits until it's
+    // reopened.
+    pub fn close_lane(&mut self, l: LaneID) {
+        self.closed_lanes.insert(l);
+    }
+
+    pub fn reopen_lane(&mut self, now: Time, l: LaneID, scheduler: &mut Scheduler, map: &Map) {
+        if self.closed_lanes.remove(&l) {
+            self.wakeup_waiting(now, map.get_l(l).src_i, scheduler, map);
+        }
+    }
+
+    pub fn is_lane_closed(&self, l: LaneID) -> bool {
+        self.closed_lanes.contains(&l)
+    }
+
+    pub fn get_throughput(&self, i: IntersectionID) -> usize {
+        self.throughput.get(&i).cloned().unwrap_or(0)
+    }
+
+    pub fn reset_throughput(&mut self) {
+        self.throughput.clear();
+    }
+
+    pub fn override_signal(
+        &mut self,
+        now: Time,
+        i: IntersectionID,
+        phase: usize,
+        scheduler: &mut Scheduler,
+        map: &Map,
+    ) {
+        let num_phases = map.get_traffic_signal(i).phases.len();
+        if phase >= num_phases {
+            panic!("{} only has {} phases, can't hold {}", i, num_phases, phase);
+        }
+        self.signal_overrides.insert(i, phase);
+        self.wakeup_waiting(now, i, scheduler, map);
+    }
+
+    pub fn clear_signal_override(
+        &mut self,
+        now: Time,
+        i: IntersectionID,
+        scheduler: &mut Scheduler,
+        map: &Map,
+    ) {
+        if self.signal_overrides.remove(&i).is_some() {
+            self.resume_signal(now, i, scheduler, map);
+        }
+    }
+
+    pub fn clear_all_signal_overrides(&mut self, now: Time, scheduler: &mut Scheduler, map: &Map) {
+        let held: Vec<IntersectionID> = self.signal_overrides.keys().cloned().collect();
+        self.signal_overrides.clear();
+        for i in held {
+            self.resume_signal(now, i, scheduler, map);
+        }
+    }
+
+    // After an override, the signal is back on its own schedule. The UpdateIntersection scheduled
+    // while it was held could be most of a phase later than the real phase change.
+    fn resume_signal(&self, now: Time, i: IntersectionID, scheduler: &mut Scheduler, map: &Map) {
+        self.wakeup_waiting(now, i, scheduler, map);
+        let (_, _, remaining) = map
+            .get_traffic_signal(i)
+            .current_phase_and_remaining_time(now);
+        scheduler.update(now + remaining, Command::UpdateIntersection(i));
+    }
+
+    pub fn get_signal_override(&self, i: IntersectionID) -> Option<usize> {
+        self.signal_overrides.get(&i).cloned()
+    }
+
+    // Like the signal's own method, but respecting overrides. A held phase never ends, but it
+    // claims to have its full duration left, so turns are still checked against something and
+    // update_intersection keeps waking up agents.
+    pub fn current_phase_and_remaining_time<'a>(
+        &self,
+        now: Time,
+        signal: &'a ControlTrafficSignal,
+    ) -> (usize, &'a Phase, Duration) {
+        if let Some(idx) = self.signal_overrides.get(&signal.id) {
+            let phase = &signal.phases[*idx];
+            return (*idx, phase, phase.duration);
+        }
+        signal.current_phase_and_remaining_time(now)
+    }
+
     fn wakeup_waiting(&self, now: Time, i: IntersectionID, scheduler: &mut Scheduler, map: &Map) {
         /*if i == IntersectionID(64) {
             println!("at {}: wakeup_waiting -----------------", now);
@@ -152,7 +254,7 @@ impl IntersectionSimState {
                 protected.push(req);
             }
         } else if let Some(ref signal) = map.maybe_get_traffic_signal(i) {
-            let (_, phase, _) = signal.current_phase_and_remaining_time(now);
+            let (_, phase, _) = self.current_phase_and_remaining_time(now, signal);
             for (req, _) in all {
                 match phase.get_priority_of_turn(req.turn, signal) {
                     TurnPriority::Protected => {
@@ -202,9 +304,8 @@ impl IntersectionSimState {
         scheduler: &mut Scheduler,
     ) {
         self.wakeup_waiting(now, id, scheduler, map);
-        let (_, _, remaining) = map
-            .get_traffic_signal(id)
-            .current_phase_and_remaining_time(now);
+        let (_, _, remaining) =
+            self.current_phase_and_remaining_time(now, map.get_traffic_signal(id));
         scheduler.push(now + remaining, Command::UpdateIntersection(id));
     }
 
@@ -237,6 +338,11 @@ impl IntersectionSimState {
             .entry(req.clone())
             .or_insert(now);
 
+        // reopen_lane will wake everybody up
+        if self.closed_lanes.contains(&turn.dst) {
+            return false;
+        }
+
         let readonly_pair = maybe_cars_and_queues.as_ref().map(|(_, c, q)| (*c, &**q));
         let allowed = if self.use_freeform_policy_everywhere {
             self.freeform_policy(&req, map, readonly_pair)
@@ -469,7 +575,7 @@ impl IntersectionSimState {
             return true;
         }
 
-        let (_, phase, remaining_phase_time) = signal.current_phase_and_remaining_time(now);
+        let (_, phase, remaining_phase_time) = self.current_phase_and_remaining_time(now, signal);
 
         // Can't go at all this phase.
         let our_priority = phase.get_priority_of_turn(req.turn, signal);
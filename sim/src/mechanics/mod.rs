@@ -5,7 +5,7 @@ mod parking;
 mod queue;
 mod walking;
 
-pub use self::driving::DrivingSimState;
+pub use self::driving::{DrivingConfig, DrivingSimState};
 pub use self::intersection::IntersectionSimState;
 pub use self::parking::ParkingSimState;
 pub use self::queue::Queue;
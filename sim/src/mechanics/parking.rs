@@ -50,6 +50,10 @@ pub struct ParkingSimState {
     )]
     driving_to_lots: MultiMap<LaneID, ParkingLotID>,
 
+    // For cars seeded at the start of a scenario, the building they were seeded near. This
+    // outlives the car being parked, since the car belongs to somebody at that building.
+    owner_bldgs: BTreeMap<CarID, BuildingID>,
+
     events: Vec<Event>,
 }
 
@@ -69,6 +73,8 @@ impl ParkingSimState {
             num_spots_per_lot: BTreeMap::new(),
             driving_to_lots: MultiMap::new(),
 
+            owner_bldgs: BTreeMap::new(),
+
             events: Vec::new(),
         };
         for l in map.all_lanes() {
@@ -150,6 +156,11 @@ impl ParkingSimState {
         }
     }
 
+    // For a car that reserved a spot, but won't ever park in it
+    pub fn unreserve_spot(&mut self, spot: ParkingSpot) {
+        assert!(self.reserved_spots.remove(&spot));
+    }
+
     pub fn remove_parked_car(&mut self, p: ParkedCar) {
         self.parked_cars
             .remove(&p.vehicle.id)
@@ -363,6 +374,54 @@ impl ParkingSimState {
         self.parked_cars.get(&id)
     }
 
+    pub fn set_owner_bldg(&mut self, id: CarID, b: BuildingID) {
+        self.owner_bldgs.insert(id, b);
+    }
+
+    // For a car that's gone for good
+    pub fn forget_owner_bldg(&mut self, id: CarID) {
+        self.owner_bldgs.remove(&id);
+    }
+
+    // Only for cars that're currently parked
+    pub fn get_parked_car_owner(&self, id: CarID) -> Option<BuildingID> {
+        if !self.parked_cars.contains_key(&id) {
+            return None;
+        }
+        self.owner_bldgs.get(&id).cloned()
+    }
+
+    pub fn get_all_parked_car_owners(&self) -> Vec<(CarID, BuildingID)> {
+        self.parked_cars
+            .keys()
+            .filter_map(|id| self.owner_bldgs.get(id).map(|b| (*id, *b)))
+            .collect()
+    }
+
+    // The fraction of spots filled on a parking lane. None for other lanes.
+    pub fn get_lane_occupancy(&self, l: LaneID) -> Option<f64> {
+        self.onstreet_lanes.get(&l).map(|lane| self.occupancy(lane))
+    }
+
+    pub fn get_all_lane_occupancy(&self) -> BTreeMap<LaneID, f64> {
+        self.onstreet_lanes
+            .iter()
+            .map(|(l, lane)| (*l, self.occupancy(lane)))
+            .collect()
+    }
+
+    fn occupancy(&self, lane: &ParkingLane) -> f64 {
+        let spots = lane.spots();
+        if spots.is_empty() {
+            return 0.0;
+        }
+        let filled = spots
+            .iter()
+            .filter(|spot| self.occupants.contains_key(*spot))
+            .count();
+        (filled as f64) / (spots.len() as f64)
+    }
+
     // (Filled, available)
     pub fn get_all_parking_spots(&self) -> (Vec<ParkingSpot>, Vec<ParkingSpot>) {
         let mut spots = Vec::new();
@@ -456,6 +515,11 @@ impl ParkingSimState {
         None
     }
 
+    // Cars that've picked a spot and are still driving to it or parking in it
+    pub fn count_searching(&self) -> usize {
+        self.reserved_spots.len()
+    }
+
     pub fn collect_events(&mut self) -> Vec<Event> {
         std::mem::replace(&mut self.events, Vec::new())
     }
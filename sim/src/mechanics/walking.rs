@@ -385,6 +385,29 @@ impl WalkingSimState {
         peds
     }
 
+    // Like DrivingSimState::current_speed. Contraflow counts as positive.
+    pub fn current_speed(&self, id: PedestrianID) -> Option<Speed> {
+        let ped = self.peds.get(&id)?;
+        if let PedState::Crossing(ref dist_int, ref time_int) = ped.state {
+            let dt = time_int.end - time_int.start;
+            if dt > Duration::ZERO {
+                return Some(Speed::meters_per_second(
+                    (dist_int.end - dist_int.start).inner_meters().abs() / dt.inner_seconds(),
+                ));
+            }
+        }
+        Some(Speed::ZERO)
+    }
+
+    pub fn num_peds(&self) -> usize {
+        self.peds.len()
+    }
+
+    // Sorted by ID
+    pub fn all_peds(&self) -> Vec<PedestrianID> {
+        self.peds.keys().cloned().collect()
+    }
+
     pub fn does_ped_exist(&self, id: PedestrianID) -> bool {
         self.peds.contains_key(&id)
     }
@@ -507,6 +530,24 @@ impl WalkingSimState {
         (loners, crowds)
     }
 
+    // (walking along a sidewalk or crosswalk, waiting to start a turn)
+    pub fn count_waiting(&self) -> (usize, usize) {
+        let mut walking = 0;
+        let mut waiting = 0;
+        for ped in self.peds.values() {
+            match ped.state {
+                PedState::Crossing(_, _) => {
+                    walking += 1;
+                }
+                PedState::WaitingToTurn(_, _) => {
+                    waiting += 1;
+                }
+                _ => {}
+            }
+        }
+        (walking, waiting)
+    }
+
     pub fn collect_events(&mut self) -> Vec<Event> {
         std::mem::replace(&mut self.events, Vec::new())
     }
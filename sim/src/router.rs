@@ -1,6 +1,7 @@
 use crate::mechanics::Queue;
 use crate::{
-    Event, ParkingSimState, ParkingSpot, PersonID, SidewalkSpot, TripID, TripPhaseType, Vehicle,
+    DrivingGoal, Event, ParkingSimState, ParkingSpot, PersonID, SidewalkSpot, TripID,
+    TripPhaseType, Vehicle,
 };
 use geom::Distance;
 use map_model::{
@@ -379,6 +380,63 @@ impl Router {
         self.path.modify_step(3, PathStep::Turn(turn2), map);
     }
 
+    // Look for a different way to the same goal from the current lane, because the next turn
+    // seems hopeless. True if the path changed. Buses stick to their route.
+    pub fn reroute(&mut self, vehicle: &Vehicle, map: &Map) -> bool {
+        self.reroute_from_current_lane(vehicle, true, map)
+    }
+
+    // After map edits, find a new way to the same goal from the current lane. False if there's no
+    // way there anymore. Buses stick to their route.
+    pub fn reroute_after_edits(&mut self, vehicle: &Vehicle, map: &Map) -> bool {
+        self.reroute_from_current_lane(vehicle, false, map)
+    }
+
+    // Pathfinds from the end of the current lane to the same goal. If must_change_next_turn, the
+    // new path is only used if it leaves this lane a different way than the old one.
+    fn reroute_from_current_lane(
+        &mut self,
+        vehicle: &Vehicle,
+        must_change_next_turn: bool,
+        map: &Map,
+    ) -> bool {
+        let constraints = vehicle.vehicle_type.to_constraints();
+        let end = match self.goal {
+            Goal::EndAtBorder { end_dist, .. } | Goal::BikeThenStop { end_dist } => {
+                Position::new(self.path.last_step().as_lane(), end_dist)
+            }
+            Goal::ParkNearBuilding {
+                spot: Some((_, end_dist)),
+                ..
+            } => Position::new(self.path.last_step().as_lane(), end_dist),
+            // No spot picked yet, so just head for the building again
+            Goal::ParkNearBuilding { target, .. } => {
+                DrivingGoal::ParkNear(target).goal_pos(constraints, map)
+            }
+            Goal::FollowBusRoute { .. } => {
+                return false;
+            }
+        };
+        let current = self.head().as_lane();
+        let req = PathRequest {
+            start: Position::new(current, map.get_l(current).length()),
+            end,
+            constraints,
+        };
+        match map.pathfind(req) {
+            Some(path) => {
+                if must_change_next_turn
+                    && !(path.isnt_last_step() && path.next_step() != self.path.next_step())
+                {
+                    return false;
+                }
+                self.path = path;
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn replace_path_for_serialization(&mut self, path: Path) -> Path {
         std::mem::replace(&mut self.path, path)
     }
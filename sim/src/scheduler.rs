@@ -23,6 +23,9 @@ pub enum Command {
     Callback(Duration),
     Pandemic(pandemic::Cmd),
     FinishRemoteTrip(TripID),
+    CheckForStuckCars,
+    // Apply all of the lane closures that're due
+    ChangeLaneClosures,
 }
 
 impl Command {
@@ -46,6 +49,8 @@ impl Command {
             Command::Callback(_) => CommandType::Callback,
             Command::Pandemic(ref p) => CommandType::Pandemic(p.clone()),
             Command::FinishRemoteTrip(t) => CommandType::FinishRemoteTrip(*t),
+            Command::CheckForStuckCars => CommandType::CheckForStuckCars,
+            Command::ChangeLaneClosures => CommandType::ChangeLaneClosures,
         }
     }
 }
@@ -62,6 +67,8 @@ pub enum CommandType {
     Callback,
     Pandemic(pandemic::Cmd),
     FinishRemoteTrip(TripID),
+    CheckForStuckCars,
+    ChangeLaneClosures,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone)]
@@ -214,6 +221,26 @@ impl Scheduler {
         }
     }
 
+    pub fn get_trip_start(&self, id: TripID) -> Option<&Command> {
+        self.queued_commands
+            .get(&CommandType::StartTrip(id))
+            .map(|(cmd, _)| cmd)
+    }
+
+    // Trips that haven't started yet, sorted by start time
+    pub fn get_scheduled_trips(&self) -> Vec<(Time, TripID)> {
+        let mut trips: Vec<(Time, TripID)> = self
+            .queued_commands
+            .iter()
+            .filter_map(|(cmd_type, (_, time))| match cmd_type {
+                CommandType::StartTrip(id) => Some((*time, *id)),
+                _ => None,
+            })
+            .collect();
+        trips.sort();
+        trips
+    }
+
     pub fn describe_stats(&self) -> String {
         format!("delta times for events: {}", self.delta_times.describe())
     }
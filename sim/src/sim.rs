@@ -1,26 +1,34 @@
 use crate::{
     AgentID, AlertLocation, Analytics, CarID, Command, CreateCar, DrawCarInput, DrawPedCrowdInput,
-    DrawPedestrianInput, DrivingSimState, Event, GetDrawAgents, IntersectionSimState, OrigPersonID,
-    PandemicModel, ParkedCar, ParkingSimState, ParkingSpot, PedestrianID, Person, PersonID,
-    PersonState, Router, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState, TripEndpoint,
-    TripID, TripManager, TripMode, TripPhaseType, TripPositions, TripResult, TripSpawner,
-    UnzoomedAgent, Vehicle, VehicleSpec, VehicleType, WalkingSimState, BUS_LENGTH, MIN_CAR_LENGTH,
+    DrawPedestrianInput, DrivingConfig, DrivingGoal, DrivingSimState, Event, GetDrawAgents,
+    IntersectionSimState, OrigPersonID, PandemicModel, ParkedCar, ParkingSimState, ParkingSpot,
+    PedestrianID, Person, PersonID, PersonState, Router, Scenario, Scheduler, SidewalkPOI,
+    SidewalkSpot, SimEvent, TransitRiderState, TransitSimState, TripEndpoint, TripID, TripManager,
+    TripMode, TripPhaseType, TripPositions, TripPurpose, TripResult, TripSpawner, TripSpec,
+    TripStats, UnzoomedAgent, Vehicle, VehicleSpec, VehicleType, WalkingSimState, BUS_LENGTH,
+    MIN_CAR_LENGTH,
 };
 use abstutil::Timer;
 use derivative::Derivative;
 use geom::{Distance, Duration, PolyLine, Pt2D, Speed, Time};
 use instant::Instant;
 use map_model::{
-    BuildingID, BusRoute, BusRouteID, IntersectionID, LaneID, Map, ParkingLotID, Path,
-    PathConstraints, PathRequest, PathStep, Position, RoadID, Traversable,
+    BuildingID, BusRoute, BusRouteID, BusStopID, IntersectionID, LaneID, Map, ParkingLotID, Path,
+    PathConstraints, PathRequest, PathStep, Position, RoadID, Traversable, TurnID,
 };
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
 use rand_xorshift::XorShiftRng;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 // TODO Do something else.
 const BLIND_RETRY_TO_SPAWN: Duration = Duration::const_seconds(5.0);
+// How often to look for cars that've waited longer than max_wait
+const STUCK_CHECK_FREQUENCY: Duration = Duration::const_seconds(30.0);
 
 #[derive(Serialize, Deserialize, Clone, Derivative)]
 #[derivative(PartialEq)]
@@ -36,6 +44,25 @@ pub struct Sim {
     pandemic: Option<PandemicModel>,
     scheduler: Scheduler,
     time: Time,
+    // See set_max_wait
+    max_wait: Option<Duration>,
+    // The cumulative number of finished trips by the end of each period, starting from midnight
+    completed_trips: Vec<usize>,
+    completed_trips_period: Duration,
+    // The mean speed of all cars at the end of each completed_trips_period
+    mean_speeds: Vec<Speed>,
+    #[derivative(PartialEq = "ignore")]
+    steady_state: SteadyStateThresholds,
+    // See Sim::get_agent_by_label
+    labels: BTreeMap<String, PersonID>,
+    // Scripted lane closures (or reopenings), sorted by time. Only the first is scheduled.
+    lane_closures: VecDeque<(Time, LaneID, bool)>,
+    // Metered spawns currently waiting for room, and how many have ever waited or given up
+    spawns_waiting: BTreeSet<CarID>,
+    num_spawns_deferred: usize,
+    num_spawns_dropped: usize,
+    // See set_border_recycle. How many vehicles have been recycled so far.
+    border_recycle: Option<u64>,
 
     // TODO Reconsider these
     pub(crate) map_name: String,
@@ -60,6 +87,16 @@ pub struct Sim {
     #[derivative(PartialEq = "ignore")]
     #[serde(skip_serializing, skip_deserializing)]
     alerts: AlertHandler,
+
+    // Only recorded after enable_event_log
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    event_log: Option<Vec<SimEvent>>,
+
+    // Only set after set_reset_point. Shared, so cloning a Sim doesn't copy this too.
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    reset_point: Option<Arc<Sim>>,
 }
 
 #[derive(Clone)]
@@ -72,6 +109,29 @@ pub struct SimOptions {
     pub enable_pandemic_model: Option<XorShiftRng>,
     pub alerts: AlertHandler,
     pub pathfinding_upfront: bool,
+    pub driving_config: DrivingConfig,
+    // How often to sample completed_trips_timeseries, and the mean speed for is_steady_state
+    pub completed_trips_period: Duration,
+    pub steady_state: SteadyStateThresholds,
+}
+
+// How much is_steady_state lets things change between two windows of time. Each is the largest
+// relative change allowed, like 0.1 for 10%.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SteadyStateThresholds {
+    // In the number of trips finished
+    pub trip_completion_rate: f64,
+    // In the mean speed of all cars on the road, including ones stuck in traffic
+    pub mean_speed: f64,
+}
+
+impl std::default::Default for SteadyStateThresholds {
+    fn default() -> SteadyStateThresholds {
+        SteadyStateThresholds {
+            trip_completion_rate: 0.1,
+            mean_speed: 0.1,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -101,6 +161,9 @@ impl SimOptions {
             enable_pandemic_model: None,
             alerts: AlertHandler::Print,
             pathfinding_upfront: false,
+            driving_config: DrivingConfig::default(),
+            completed_trips_period: Duration::minutes(1),
+            steady_state: SteadyStateThresholds::default(),
         }
     }
 }
@@ -108,9 +171,15 @@ impl SimOptions {
 // Setup
 impl Sim {
     pub fn new(map: &Map, opts: SimOptions, timer: &mut Timer) -> Sim {
+        if opts.completed_trips_period <= Duration::ZERO {
+            panic!(
+                "completed_trips_period must be positive, not {}",
+                opts.completed_trips_period
+            );
+        }
         let mut scheduler = Scheduler::new();
         Sim {
-            driving: DrivingSimState::new(map, opts.recalc_lanechanging),
+            driving: DrivingSimState::new(map, opts.recalc_lanechanging, opts.driving_config),
             parking: ParkingSimState::new(map, timer),
             walking: WalkingSimState::new(),
             intersections: IntersectionSimState::new(
@@ -129,6 +198,17 @@ impl Sim {
             },
             scheduler,
             time: Time::START_OF_DAY,
+            max_wait: None,
+            completed_trips: Vec::new(),
+            completed_trips_period: opts.completed_trips_period,
+            mean_speeds: Vec::new(),
+            steady_state: opts.steady_state,
+            labels: BTreeMap::new(),
+            lane_closures: VecDeque::new(),
+            spawns_waiting: BTreeSet::new(),
+            num_spawns_deferred: 0,
+            num_spawns_dropped: 0,
+            border_recycle: None,
 
             map_name: map.get_name().to_string(),
             // TODO
@@ -139,6 +219,8 @@ impl Sim {
             alerts: opts.alerts,
 
             analytics: Analytics::new(),
+            event_log: None,
+            reset_point: None,
         }
     }
 
@@ -223,18 +305,42 @@ impl Sim {
         &mut self,
         p: PersonID,
         orig_id: Option<OrigPersonID>,
+        provenance: Option<String>,
         ped_speed: Speed,
         vehicle_specs: Vec<VehicleSpec>,
     ) {
-        self.trips.new_person(p, orig_id, ped_speed, vehicle_specs);
+        self.trips
+            .new_person(p, orig_id, provenance, ped_speed, vehicle_specs);
+    }
+    // Fails without changing anything if someone else already has the label
+    pub(crate) fn set_person_label(&mut self, p: PersonID, label: String) -> Result<(), String> {
+        if let Some(other) = self.labels.get(&label) {
+            return Err(format!("{} and {} both have the label {}", other, p, label));
+        }
+        self.labels.insert(label, p);
+        Ok(())
     }
     pub fn random_person(&mut self, ped_speed: Speed, vehicle_specs: Vec<VehicleSpec>) -> &Person {
         self.trips.random_person(ped_speed, vehicle_specs)
     }
-    pub(crate) fn seed_parked_car(&mut self, vehicle: Vehicle, spot: ParkingSpot) {
+    pub(crate) fn seed_parked_car(
+        &mut self,
+        vehicle: Vehicle,
+        spot: ParkingSpot,
+        owner_bldg: Option<BuildingID>,
+    ) {
+        if let Some(b) = owner_bldg {
+            self.parking.set_owner_bldg(vehicle.id, b);
+        }
         self.parking.reserve_spot(spot);
         self.parking.add_parked_car(ParkedCar { vehicle, spot });
     }
+    // Nobody will ever drive this car
+    pub(crate) fn seed_unowned_parked_car(&mut self, spec: VehicleSpec, spot: ParkingSpot) {
+        let id = CarID(self.trips.new_car_id(), spec.vehicle_type);
+        let vehicle = spec.make(id, None);
+        self.seed_parked_car(vehicle, spot, None);
+    }
 
     pub fn seed_bus_route(&mut self, route: &BusRoute, map: &Map, timer: &mut Timer) -> Vec<CarID> {
         let mut results: Vec<CarID> = Vec::new();
@@ -285,6 +391,7 @@ impl Sim {
                         router: Router::follow_bus_route(path.clone(), end_dist),
                         maybe_parked_car: None,
                         trip_and_person: None,
+                        give_up_at: None,
                     },
                     map,
                     &self.intersections,
@@ -426,6 +533,7 @@ impl Sim {
         maybe_cb: &mut Option<Box<dyn SimCallback>>,
     ) -> bool {
         self.time = time;
+        self.sample_mean_speed();
         let mut events = Vec::new();
         let mut halt = false;
         match cmd {
@@ -450,6 +558,7 @@ impl Sim {
                     &self.parking,
                     &mut self.scheduler,
                 ) {
+                    self.spawns_waiting.remove(&create_car.vehicle.id);
                     if let Some((trip, _)) = create_car.trip_and_person {
                         self.trips
                             .agent_starting_trip_leg(AgentID::Car(create_car.vehicle.id), trip);
@@ -478,7 +587,29 @@ impl Sim {
                     }
                     self.analytics
                         .record_demand(create_car.router.get_path(), map);
+                } else if create_car
+                    .give_up_at
+                    .map(|t| self.time + BLIND_RETRY_TO_SPAWN > t)
+                    .unwrap_or(false)
+                {
+                    // A metered spawn that would miss its window. The vehicle never entered the
+                    // map, so there's nothing to clean up.
+                    self.spawns_waiting.remove(&create_car.vehicle.id);
+                    self.num_spawns_dropped += 1;
+                    let (trip, _) = create_car.trip_and_person.unwrap();
+                    self.trips.cancel_trip(
+                        self.time,
+                        trip,
+                        &mut self.parking,
+                        &mut self.scheduler,
+                        map,
+                    );
                 } else if retry_if_no_room {
+                    if create_car.give_up_at.is_some()
+                        && self.spawns_waiting.insert(create_car.vehicle.id)
+                    {
+                        self.num_spawns_deferred += 1;
+                    }
                     // TODO Record this in the trip log
                     self.scheduler.push(
                         self.time + BLIND_RETRY_TO_SPAWN,
@@ -552,6 +683,9 @@ impl Sim {
                     &mut self.transit,
                     &mut self.walking,
                 );
+                for id in self.driving.take_failed_reroutes() {
+                    self.kill_stuck_car(id, map);
+                }
             }
             Command::UpdateLaggyHead(car) => {
                 self.driving.update_laggy_head(
@@ -600,6 +734,56 @@ impl Sim {
                     &mut self.scheduler,
                 );
             }
+            Command::ChangeLaneClosures => {
+                while self
+                    .lane_closures
+                    .front()
+                    .map(|(t, _, _)| *t <= self.time)
+                    .unwrap_or(false)
+                {
+                    let (_, l, closed) = self.lane_closures.pop_front().unwrap();
+                    if closed {
+                        self.intersections.close_lane(l);
+                    } else {
+                        self.intersections
+                            .reopen_lane(self.time, l, &mut self.scheduler, map);
+                    }
+                }
+                if let Some((t, _, _)) = self.lane_closures.front() {
+                    self.scheduler.push(*t, Command::ChangeLaneClosures);
+                }
+            }
+            Command::CheckForStuckCars => {
+                if let Some(max_wait) = self.max_wait {
+                    self.scheduler.push(
+                        self.time + STUCK_CHECK_FREQUENCY,
+                        Command::CheckForStuckCars,
+                    );
+                    for id in self.driving.find_stuck_cars(self.time, max_wait) {
+                        if self.driving.reroute_stuck_car(
+                            id,
+                            self.time,
+                            map,
+                            &mut self.scheduler,
+                            &mut self.intersections,
+                        ) {
+                            continue;
+                        }
+                        let loc = match self.agent_to_person(AgentID::Car(id)) {
+                            Some(p) => AlertLocation::Person(p),
+                            None => AlertLocation::Nil,
+                        };
+                        events.push(Event::Alert(
+                            loc,
+                            format!(
+                                "{} waited more than {} and couldn't reroute, so it was removed",
+                                id, max_wait
+                            ),
+                        ));
+                        self.kill_stuck_car(id, map);
+                    }
+                }
+            }
         }
 
         // Record events at precisely the time they occur.
@@ -615,13 +799,118 @@ impl Sim {
         events.extend(self.walking.collect_events());
         events.extend(self.intersections.collect_events());
         events.extend(self.parking.collect_events());
+        let mut recycle = Vec::new();
         for ev in events {
             if let Some(ref mut m) = self.pandemic {
                 m.handle_event(self.time, &ev, &mut self.scheduler);
             }
+            if let Some(ref mut log) = self.event_log {
+                log.extend(SimEvent::from_event(self.time, &ev));
+            }
+            if let Event::TripFinished { trip, mode, .. } = ev {
+                self.record_completed_trip();
+                if self.border_recycle.is_some() && self.trips.vehicle_finished_at_border(trip) {
+                    recycle.push(mode);
+                }
+            }
 
             self.analytics.event(ev, self.time, map);
         }
+        for mode in recycle {
+            self.recycle_border_trip(mode, map);
+        }
+    }
+
+    // Replace a vehicle that just left the map with a new one, entering from a random border and
+    // heading to another.
+    fn recycle_border_trip(&mut self, mode: TripMode, map: &Map) {
+        // Seeding from the count, instead of keeping an RNG around, means this picks up where it
+        // left off after loading a savestate.
+        let count = self.border_recycle.unwrap();
+        self.border_recycle = Some(count + 1);
+        let mut rng = XorShiftRng::seed_from_u64(42 + count);
+        let (constraints, spec) = if mode == TripMode::Bike {
+            (PathConstraints::Bike, Scenario::rand_bike(&mut rng))
+        } else {
+            (PathConstraints::Car, Scenario::rand_car(&mut rng))
+        };
+        let starts: Vec<Position> = map
+            .all_incoming_borders()
+            .into_iter()
+            .flat_map(|i| i.get_outgoing_lanes(map, constraints))
+            .filter_map(|l| {
+                TripSpec::spawn_vehicle_at(Position::new(l, Distance::ZERO), spec.length, map)
+            })
+            .collect();
+        let goals: Vec<DrivingGoal> = map
+            .all_outgoing_borders()
+            .into_iter()
+            .filter_map(|i| {
+                DrivingGoal::end_at_border(i.some_incoming_road(map)?, constraints, None, map)
+            })
+            .collect();
+        if let (Some(start_pos), Some(goal)) = (
+            starts.choose(&mut rng).cloned(),
+            goals.choose(&mut rng).cloned(),
+        ) {
+            let person = self
+                .random_person(Scenario::rand_ped_speed(&mut rng), vec![spec])
+                .id;
+            let car = self.get_person(person).vehicles[0].id;
+            let mut spawner = self.make_spawner();
+            spawner.schedule_trip(
+                self.get_person(person),
+                self.time,
+                TripSpec::VehicleAppearing {
+                    start_pos,
+                    goal,
+                    use_vehicle: car,
+                    retry_if_no_room: true,
+                    origin: None,
+                    give_up_at: None,
+                },
+                TripEndpoint::Border(map.get_l(start_pos.lane()).src_i, None),
+                TripPurpose::Unknown,
+                map,
+            );
+            self.flush_spawner(spawner, map, &mut Timer::throwaway());
+        }
+    }
+
+    // Sampled the first time the sim passes the end of each period. If nothing happens for a few
+    // periods, they all get the same speed, which is fine because nothing moved anyway.
+    fn sample_mean_speed(&mut self) {
+        let idx = (self.time.inner_seconds() / self.completed_trips_period.inner_seconds()).floor()
+            as usize;
+        if self.mean_speeds.len() < idx {
+            let speed = self.driving.mean_speed().unwrap_or(Speed::ZERO);
+            while self.mean_speeds.len() < idx {
+                self.mean_speeds.push(speed);
+            }
+        }
+    }
+
+    // How many trips finished before this period started
+    fn completed_trips_before(&self, period: usize) -> usize {
+        if period == 0 {
+            return 0;
+        }
+        self.completed_trips
+            .get(period - 1)
+            .or_else(|| self.completed_trips.last())
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    fn record_completed_trip(&mut self) {
+        let idx = (self.time.inner_seconds() / self.completed_trips_period.inner_seconds()).floor()
+            as usize;
+        // Carry the total forward through any periods where nothing finished
+        let total = self.completed_trips.last().cloned().unwrap_or(0);
+        while self.completed_trips.len() <= idx {
+            self.completed_trips.push(total);
+        }
+        self.completed_trips[idx] += 1;
     }
 
     pub fn timed_step(
@@ -679,6 +968,26 @@ impl Sim {
         );
     }
 
+    // Advances until the predicate is true (checked after every event) or max has passed, whichever
+    // comes first. Returns how far the sim advanced. For debugging, like stepping until
+    // something's stuck.
+    pub fn step_until<F: Fn(&Sim) -> bool>(
+        &mut self,
+        map: &Map,
+        predicate: F,
+        max: Duration,
+        maybe_cb: &mut Option<Box<dyn SimCallback>>,
+    ) -> Duration {
+        let start_time = self.time;
+        let end_time = self.time + max;
+        while self.time < end_time && !predicate(self) {
+            if self.minimal_step(map, end_time - self.time, maybe_cb) {
+                break;
+            }
+        }
+        self.time - start_time
+    }
+
     pub fn time_limited_step(
         &mut self,
         map: &Map,
@@ -761,12 +1070,13 @@ impl Sim {
             if dt_real >= Duration::seconds(1.0) {
                 let (finished, unfinished, _) = self.num_trips();
                 println!(
-                    "{}: {} trips finished, {} unfinished, speed = {:.2}x, {}",
+                    "{}: {} trips finished, {} unfinished, speed = {:.2}x, {}, {}",
                     self.time(),
                     abstutil::prettyprint_usize(finished),
                     abstutil::prettyprint_usize(unfinished),
                     (self.time() - last_sim_time) / dt_real,
-                    self.scheduler.describe_stats()
+                    self.scheduler.describe_stats(),
+                    self.describe_agents()
                 );
                 last_print = Instant::now();
                 last_sim_time = self.time();
@@ -789,6 +1099,149 @@ impl Sim {
     }
 }
 
+// Determinism checks
+impl Sim {
+    // Compare two sims that should be identical -- usually the same scenario run twice -- and
+    // describe where they first diverge. This is just PartialEq, with a description of the first
+    // field that differs.
+    pub fn assert_equal(&self, other: &Sim) -> Result<(), String> {
+        if self == other {
+            return Ok(());
+        }
+
+        macro_rules! check {
+            ($($field:ident),*) => {
+                $(
+                    if self.$field != other.$field {
+                        return Err(diff_json(stringify!($field), &self.$field, &other.$field));
+                    }
+                )*
+            };
+        }
+
+        if self.time != other.time {
+            return Err(format!("time: {} vs {}", self.time, other.time));
+        }
+        if self.map_name != other.map_name {
+            return Err(format!("map_name: {} vs {}", self.map_name, other.map_name));
+        }
+        if self.edits_name != other.edits_name {
+            return Err(format!(
+                "edits_name: {} vs {}",
+                self.edits_name, other.edits_name
+            ));
+        }
+        if self.scheduler != other.scheduler {
+            return Err(diff_json("scheduler", &self.scheduler, &other.scheduler));
+        }
+        if self.trips != other.trips {
+            return Err(diff_json("trips", &self.trips, &other.trips));
+        }
+        if self.driving != other.driving {
+            return Err(diff_json("driving", &self.driving, &other.driving));
+        }
+        if self.parking != other.parking {
+            return Err(diff_json("parking", &self.parking, &other.parking));
+        }
+        if self.walking != other.walking {
+            return Err(diff_json("walking", &self.walking, &other.walking));
+        }
+        if self.intersections != other.intersections {
+            return Err(diff_json(
+                "intersections",
+                &self.intersections,
+                &other.intersections,
+            ));
+        }
+        if self.transit != other.transit {
+            return Err(diff_json("transit", &self.transit, &other.transit));
+        }
+        check!(
+            max_wait,
+            completed_trips,
+            completed_trips_period,
+            mean_speeds,
+            labels,
+            lane_closures,
+            spawns_waiting,
+            num_spawns_deferred,
+            num_spawns_dropped,
+            border_recycle
+        );
+        // A new field that's compared, but not described above
+        Err("The sims differ in a field assert_equal doesn't describe".to_string())
+    }
+}
+
+// Only called once PartialEq says the two differ, so there should be a differing line.
+fn diff_json<T: Serialize>(field: &str, a: &T, b: &T) -> String {
+    let json1 = abstutil::to_json(a);
+    let json2 = abstutil::to_json(b);
+    let mut lines1 = json1.lines();
+    let mut lines2 = json2.lines();
+    let mut line_num = 1;
+    loop {
+        match (lines1.next(), lines2.next()) {
+            (Some(l1), Some(l2)) => {
+                if l1 != l2 {
+                    return format!(
+                        "{} differs at line {}: {} vs {}",
+                        field,
+                        line_num,
+                        l1.trim(),
+                        l2.trim()
+                    );
+                }
+            }
+            (Some(_), None) | (None, Some(_)) => {
+                return format!("{} differs in length after line {}", field, line_num - 1);
+            }
+            (None, None) => {
+                // Something PartialEq looks at, but serialization skips
+                return format!("{} differs, but not in its serialized form", field);
+            }
+        }
+        line_num += 1;
+    }
+}
+
+// Instantiates the scenario in a fresh Sim and runs it for up to `until`, or until everything's
+// done. Setting cancel from another thread stops the run early; whatever finished by then is
+// still reported.
+pub fn run_scenario_headless(
+    scenario: &Scenario,
+    map: &Map,
+    rng: &mut XorShiftRng,
+    until: Duration,
+    cancel: &AtomicBool,
+    timer: &mut Timer,
+) -> TripStats {
+    let mut sim = Sim::new(map, SimOptions::new(&scenario.scenario_name), timer);
+    scenario.instantiate(&mut sim, map, rng, timer);
+
+    let end_time = sim.time() + until;
+    let mut last_note = sim.time();
+    while !sim.is_done() && sim.time() < end_time && !cancel.load(Ordering::Relaxed) {
+        let before = sim.time();
+        let dt = (end_time - before).min(Duration::minutes(1));
+        sim.timed_step(map, dt, &mut None, &mut Timer::throwaway());
+        if sim.time() == before {
+            timer.warn(format!("Sim stopped advancing at {}", before));
+            break;
+        }
+        if sim.time() - last_note >= Duration::hours(1) {
+            timer.note(sim.summary());
+            last_note = sim.time();
+        }
+    }
+    if cancel.load(Ordering::Relaxed) {
+        timer.note(format!("Canceled at {}", sim.time()));
+    }
+
+    let (_, unfinished, _) = sim.num_trips();
+    sim.get_analytics().trip_stats(unfinished)
+}
+
 // Savestating
 impl Sim {
     pub fn save_dir(&self) -> String {
@@ -807,6 +1260,15 @@ impl Sim {
     }
 
     pub fn save(&mut self) -> String {
+        let path = self.save_path(self.time);
+        self.save_savestate(path.clone());
+        path
+    }
+
+    // Everything needed to resume later is captured, including trips scheduled for the future
+    // that haven't started yet. Paths for queued spawns aren't saved; load_savestate recalculates
+    // them.
+    pub fn save_savestate(&mut self, path: String) {
         let restore = self.scheduler.before_savestate();
 
         if true {
@@ -841,12 +1303,9 @@ impl Sim {
             );
         }
 
-        let path = self.save_path(self.time);
-        abstutil::write_binary(path.clone(), self);
+        abstutil::write_binary(path, self);
 
         self.scheduler.after_savestate(restore);
-
-        path
     }
 
     pub fn find_previous_savestate(&self, base_time: Time) -> Option<String> {
@@ -875,6 +1334,27 @@ impl Sim {
         );
         self.scheduler.after_savestate(paths);
     }
+
+    // Remember the current state, so reset can come back to it. Call this right after
+    // instantiating a scenario. It keeps a full copy of the sim in memory.
+    pub fn set_reset_point(&mut self) {
+        self.reset_point = None;
+        self.reset_point = Some(Arc::new(self.clone()));
+    }
+
+    // Go back to exactly the state from set_reset_point, with the same scheduled trips and any
+    // paths already calculated, without instantiating the scenario again. Running again from
+    // here reproduces the first run.
+    pub fn reset(&mut self, map: &Map) {
+        let start = self
+            .reset_point
+            .clone()
+            .expect("reset needs set_reset_point to have been called first");
+        *self = (*start).clone();
+        self.reset_point = Some(start);
+        self.intersections
+            .clear_all_signal_overrides(self.time, &mut self.scheduler, map);
+    }
 }
 
 // Queries of all sorts
@@ -887,6 +1367,12 @@ impl Sim {
         self.trips.is_done()
     }
 
+    // Trips that haven't started yet or are still underway. Finished and aborted trips don't
+    // count, so this reaches 0 when everything's done.
+    pub fn num_trips_remaining(&self) -> usize {
+        self.trips.num_trips_remaining()
+    }
+
     pub fn is_empty(&self) -> bool {
         self.time == Time::START_OF_DAY && self.is_done()
     }
@@ -900,6 +1386,45 @@ impl Sim {
         self.trips.num_ppl()
     }
 
+    pub fn snapshot_metrics(&self) -> MetricsRow {
+        let (finished_trips, unfinished_trips, _) = self.num_trips();
+        let (peds_walking, peds_waiting) = self.walking.count_waiting();
+        MetricsRow {
+            time: self.time,
+            active_cars: self.driving.num_cars(),
+            cars_waiting: self.driving.count_blocked(),
+            cars_searching_for_parking: self.parking.count_searching(),
+            peds: self.walking.num_peds(),
+            peds_walking,
+            peds_waiting,
+            mean_speed: self.driving.mean_speed(),
+            finished_trips,
+            unfinished_trips,
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let (finished, unfinished, _) = self.num_trips();
+        format!(
+            "{}: {} trips finished, {} unfinished, {}",
+            self.time,
+            abstutil::prettyprint_usize(finished),
+            abstutil::prettyprint_usize(unfinished),
+            self.describe_agents()
+        )
+    }
+
+    fn describe_agents(&self) -> String {
+        let m = self.snapshot_metrics();
+        format!(
+            "{} peds walking, {} peds waiting to cross, {} cars headed to parking, {} cars blocked",
+            abstutil::prettyprint_usize(m.peds_walking),
+            abstutil::prettyprint_usize(m.peds_waiting),
+            abstutil::prettyprint_usize(m.cars_searching_for_parking),
+            abstutil::prettyprint_usize(m.cars_waiting),
+        )
+    }
+
     pub fn debug_ped(&self, id: PedestrianID) {
         self.walking.debug_ped(id);
         self.trips.debug_trip(AgentID::Pedestrian(id));
@@ -918,6 +1443,83 @@ impl Sim {
         self.driving.debug_lane(id);
     }
 
+    // Everything moving and parked, meant for people to read and attach to bug reports. Unlike
+    // savestates, this can't be loaded. It's slow, so only call it on demand.
+    pub fn debug_dump(&self, map: &Map) -> String {
+        let mut lines = vec![self.summary(), String::new(), "Cars:".to_string()];
+        lines.extend(self.driving.debug_dump());
+
+        lines.push(String::new());
+        lines.push("Pedestrians:".to_string());
+        for ped in self.get_all_draw_peds(map) {
+            let mut line = format!("{} on {:?} at {}", ped.id, ped.on, ped.pos);
+            if let Some(t) = ped.waiting_for_turn {
+                line.push_str(&format!(", waiting for {}", t));
+            }
+            if ped.waiting_for_bus {
+                line.push_str(", waiting for a bus");
+            }
+            lines.push(line);
+        }
+
+        lines.push(String::new());
+        lines.push("Parking:".to_string());
+        for (l, occupancy) in self.parking.get_all_lane_occupancy() {
+            lines.push(format!("{}: {}% full", l, (occupancy * 100.0).round()));
+        }
+
+        lines.join("\n")
+    }
+
+    // Everything interesting about an agent right now, for UIs to show however they like. None if
+    // the agent doesn't exist.
+    pub fn describe_agent(&self, id: AgentID, map: &Map) -> Option<AgentDescription> {
+        let (on, waiting_for, speed) = match id {
+            AgentID::Car(c) => {
+                let draw = self.get_draw_car(c, map)?;
+                (
+                    draw.on,
+                    draw.waiting_for_turn,
+                    self.driving.current_speed(c),
+                )
+            }
+            AgentID::Pedestrian(p) => {
+                let draw = self.get_draw_ped(p, map)?;
+                (
+                    draw.on,
+                    draw.waiting_for_turn,
+                    self.walking.current_speed(p),
+                )
+            }
+            // Wherever the bus is
+            AgentID::BusPassenger(_, bus) => {
+                let draw = self.get_draw_car(bus, map)?;
+                (
+                    draw.on,
+                    draw.waiting_for_turn,
+                    self.driving.current_speed(bus),
+                )
+            }
+        };
+        let (mode, destination) = match self.agent_to_trip(id) {
+            Some(trip) => {
+                let (_, _, end, mode) = self.trip_info(trip);
+                (Some(mode), Some(end))
+            }
+            None => (None, None),
+        };
+        Some(AgentDescription {
+            id,
+            on,
+            // Parked cars aren't in DrivingSimState
+            speed: speed.unwrap_or(Speed::ZERO),
+            mode,
+            destination,
+            waiting_for,
+            elapsed: self.get_trip_elapsed(id),
+        })
+    }
+
     // Only call for active agents, will panic otherwise
     pub fn agent_properties(&self, id: AgentID) -> AgentProperties {
         match id {
@@ -960,6 +1562,29 @@ impl Sim {
         self.trips.active_agents()
     }
 
+    // Every car, bus, bike, and pedestrian on the map right now, in a stable order, so stats
+    // don't depend on how something happens to be stored. Unlike active_agents, buses are
+    // included. People riding a bus aren't; the bus represents them.
+    pub fn all_agents_sorted(&self) -> Vec<AgentID> {
+        let mut agents: Vec<AgentID> = self
+            .driving
+            .all_cars()
+            .into_iter()
+            .map(AgentID::Car)
+            .chain(self.walking.all_peds().into_iter().map(AgentID::Pedestrian))
+            .collect();
+        agents.sort();
+        agents
+    }
+
+    // (agent, route, boarding stop, alighting stop, state) for everybody currently on a trip that
+    // uses transit, whether they're still walking to the stop, waiting there, or riding
+    pub fn get_active_transit_trips(
+        &self,
+    ) -> Vec<(AgentID, BusRouteID, BusStopID, BusStopID, TransitRiderState)> {
+        self.trips.active_transit_trips(&self.transit)
+    }
+
     pub fn agent_to_trip(&self, id: AgentID) -> Option<TripID> {
         self.trips.agent_to_trip(id)
     }
@@ -968,10 +1593,28 @@ impl Sim {
         self.trips.trip_to_agent(id)
     }
 
+    // How long the agent's current trip has been underway
+    pub fn get_trip_elapsed(&self, id: AgentID) -> Option<Duration> {
+        let trip = self.agent_to_trip(id)?;
+        self.trips.trip_elapsed(trip, self.time)
+    }
+
     // (start time, start position, end position, trip type)
     pub fn trip_info(&self, id: TripID) -> (Time, TripEndpoint, TripEndpoint, TripMode) {
         self.trips.trip_info(id)
     }
+    // Trips that haven't started yet, sorted by start time, with (start time, trip, start
+    // position, end position, trip type)
+    pub fn get_scheduled_trips(&self) -> Vec<(Time, TripID, TripEndpoint, TripEndpoint, TripMode)> {
+        self.scheduler
+            .get_scheduled_trips()
+            .into_iter()
+            .map(|(time, id)| {
+                let (_, start, end, mode) = self.trips.trip_info(id);
+                (time, id, start, end, mode)
+            })
+            .collect()
+    }
     // If trip is finished, returns (total time, total waiting time)
     pub fn finished_trip_time(&self, id: TripID) -> Option<(Duration, Duration)> {
         self.trips.finished_trip_time(id)
@@ -980,6 +1623,86 @@ impl Sim {
     pub fn trip_to_person(&self, id: TripID) -> PersonID {
         self.trips.trip_to_person(id)
     }
+    // The cumulative number of trips finished by the end of each sampling period (one sim-minute
+    // by default). Stops at the last period where a trip finished.
+    pub fn completed_trips_timeseries(&self) -> Vec<(Time, usize)> {
+        self.completed_trips
+            .iter()
+            .enumerate()
+            .map(|(idx, cnt)| {
+                (
+                    Time::START_OF_DAY + self.completed_trips_period * ((idx + 1) as f64),
+                    *cnt,
+                )
+            })
+            .collect()
+    }
+
+    // How many vehicles have finished a turn through this intersection since the sim started, or
+    // since the last reset_intersection_throughput. Pedestrians aren't counted.
+    pub fn get_intersection_throughput(&self, i: IntersectionID) -> usize {
+        self.intersections.get_throughput(i)
+    }
+
+    // The phase a traffic signal is in right now, and how much longer it lasts. This respects
+    // override_signal.
+    pub fn current_signal_phase(&self, i: IntersectionID, map: &Map) -> (usize, Duration) {
+        let (idx, _, remaining) = self
+            .intersections
+            .current_phase_and_remaining_time(self.time, map.get_traffic_signal(i));
+        (idx, remaining)
+    }
+
+    pub fn get_signal_override(&self, i: IntersectionID) -> Option<usize> {
+        self.intersections.get_signal_override(i)
+    }
+
+    // Roughly, has the simulation settled down? This compares the last window of time with the
+    // one before it. The number of trips finished and the mean speed of cars on the road both
+    // have to be within SimOptions::steady_state of each other. The window is rounded down to a
+    // multiple of completed_trips_period. False until two whole windows have passed, or if no
+    // trips finished in either.
+    pub fn is_steady_state(&self, window: Duration) -> bool {
+        if window <= Duration::ZERO || self.completed_trips_period <= Duration::ZERO {
+            return false;
+        }
+        let periods = (window / self.completed_trips_period).floor() as usize;
+        let done = self.mean_speeds.len();
+        if periods == 0 || done < 2 * periods {
+            return false;
+        }
+
+        let recent_trips =
+            self.completed_trips_before(done) - self.completed_trips_before(done - periods);
+        let prev_trips = self.completed_trips_before(done - periods)
+            - self.completed_trips_before(done - 2 * periods);
+        if recent_trips + prev_trips == 0 {
+            return false;
+        }
+        let mean = |speeds: &[Speed]| {
+            speeds
+                .iter()
+                .map(|s| s.inner_meters_per_second())
+                .sum::<f64>()
+                / (speeds.len() as f64)
+        };
+        let recent_speed = mean(&self.mean_speeds[done - periods..done]);
+        let prev_speed = mean(&self.mean_speeds[done - 2 * periods..done - periods]);
+
+        relative_change(recent_trips as f64, prev_trips as f64)
+            <= self.steady_state.trip_completion_rate
+            && relative_change(recent_speed, prev_speed) <= self.steady_state.mean_speed
+    }
+
+    // How many metered border spawns had to wait for room, and how many of those gave up because
+    // their window closed first
+    pub fn metered_spawns(&self) -> (usize, usize) {
+        (self.num_spawns_deferred, self.num_spawns_dropped)
+    }
+
+    pub fn trip_purpose(&self, id: TripID) -> TripPurpose {
+        self.trips.trip_purpose(id)
+    }
     // TODO This returns None for parked cars owned by people! That's confusing. Dedupe with
     // get_owner_of_car.
     pub fn agent_to_person(&self, id: AgentID) -> Option<PersonID> {
@@ -993,6 +1716,13 @@ impl Sim {
     pub fn lookup_parked_car(&self, id: CarID) -> Option<&ParkedCar> {
         self.parking.lookup_parked_car(id)
     }
+    // The building a currently parked car was seeded for, if it was seeded with an owner
+    pub fn get_parked_car_owner(&self, id: CarID) -> Option<BuildingID> {
+        self.parking.get_parked_car_owner(id)
+    }
+    pub fn get_all_parked_car_owners(&self) -> Vec<(CarID, BuildingID)> {
+        self.parking.get_all_parked_car_owners()
+    }
 
     pub fn lookup_person(&self, id: PersonID) -> Option<&Person> {
         self.trips.get_person(id)
@@ -1000,6 +1730,17 @@ impl Sim {
     pub fn get_person(&self, id: PersonID) -> &Person {
         self.trips.get_person(id).unwrap()
     }
+    // Labels come from PersonSpec and stay the same across runs, unlike the numeric IDs.
+    pub fn get_person_by_label(&self, label: &str) -> Option<PersonID> {
+        self.labels.get(label).cloned()
+    }
+    // The agent the labeled person is currently controlling, if they're in the middle of a trip
+    pub fn get_agent_by_label(&self, label: &str) -> Option<AgentID> {
+        match self.get_person(self.get_person_by_label(label)?).state {
+            PersonState::Trip(t) => self.trip_to_agent(t).ok(),
+            _ => None,
+        }
+    }
     pub fn find_person_by_orig_id(&self, id: OrigPersonID) -> Option<PersonID> {
         for p in self.get_all_people() {
             if p.orig_id == Some(id) {
@@ -1040,6 +1781,45 @@ impl Sim {
         self.driving.get_all_driving_paths()
     }
 
+    // The lanes and turns an agent still intends to cross. If the agent hasn't spawned yet, this
+    // is the path calculated upfront for its next trip, which only exists when paths are
+    // calculated upfront.
+    pub fn get_agent_path(&self, id: AgentID) -> Option<Vec<Traversable>> {
+        let path = match self.get_path(id) {
+            Some(path) => path,
+            None => self.get_upfront_path(id)?,
+        };
+        Some(
+            path.get_steps()
+                .iter()
+                .map(|step| step.as_traversable())
+                .collect(),
+        )
+    }
+
+    fn get_upfront_path(&self, id: AgentID) -> Option<&Path> {
+        for (_, trip) in self.scheduler.get_scheduled_trips() {
+            if let Some(Command::StartTrip(_, spec, _, Some(path))) =
+                self.scheduler.get_trip_start(trip)
+            {
+                // Which agent will follow the path
+                let agent = match spec {
+                    TripSpec::VehicleAppearing { use_vehicle, .. } => AgentID::Car(*use_vehicle),
+                    TripSpec::JustWalking { .. }
+                    | TripSpec::UsingBike { .. }
+                    | TripSpec::UsingTransit { .. } => {
+                        AgentID::Pedestrian(self.get_person(self.trip_to_person(trip)).ped)
+                    }
+                    _ => continue,
+                };
+                if agent == id {
+                    return Some(path);
+                }
+            }
+        }
+        None
+    }
+
     pub fn trace_route(
         &self,
         id: AgentID,
@@ -1099,6 +1879,41 @@ impl Sim {
         }
     }
 
+    // Moving and parked cars and pedestrians within the radius, closest first. Bus passengers
+    // aren't included; look for their bus.
+    pub fn get_agents_near(&self, pt: Pt2D, radius: Distance, map: &Map) -> Vec<AgentID> {
+        let mut results: Vec<(AgentID, Distance)> = self
+            .all_agent_positions(map)
+            .into_iter()
+            .filter_map(|(id, pos)| {
+                let dist = pt.dist_to(pos);
+                if dist <= radius {
+                    Some((id, dist))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        results.sort_by_key(|(_, dist)| *dist);
+        results.into_iter().map(|(id, _)| id).collect()
+    }
+
+    // A linear scan for now. If get_agents_near gets used heavily, a spatial index, updated as
+    // the sim steps, could replace this.
+    fn all_agent_positions(&self, map: &Map) -> Vec<(AgentID, Pt2D)> {
+        let mut results: Vec<(AgentID, Pt2D)> = self
+            .get_all_draw_cars(map)
+            .into_iter()
+            .map(|c| (AgentID::Car(c.id), c.body.last_pt()))
+            .collect();
+        results.extend(
+            self.get_all_draw_peds(map)
+                .into_iter()
+                .map(|p| (AgentID::Pedestrian(p.id), p.pos)),
+        );
+        results
+    }
+
     pub fn get_accepted_agents(&self, id: IntersectionID) -> HashSet<AgentID> {
         self.intersections.get_accepted_agents(id)
     }
@@ -1143,6 +1958,69 @@ impl Sim {
             .delayed_intersections(self.time, threshold)
     }
 
+    // Cars that've been blocked for at least threshold, with how long. Longest first.
+    pub fn get_stuck_agents(&self, threshold: Duration) -> Vec<(CarID, Duration)> {
+        self.driving.get_stuck_cars(self.time, threshold)
+    }
+
+    // For cycling through get_stuck_agents: the car after prev, or the most stuck car if prev is
+    // None or isn't stuck anymore.
+    pub fn next_stuck_agent(&self, threshold: Duration, prev: Option<CarID>) -> Option<CarID> {
+        let stuck = self.get_stuck_agents(threshold);
+        let idx = prev
+            .and_then(|prev| stuck.iter().position(|(id, _)| *id == prev))
+            .map(|idx| (idx + 1) % stuck.len())
+            .unwrap_or(0);
+        stuck.get(idx).map(|(id, _)| *id)
+    }
+
+    // The cars on a lane, from the front (closest to the intersection) to the back, with the
+    // distance of their front along the lane and how long they've been waiting. Cars that are
+    // moving have waited for zero.
+    pub fn get_lane_queue(&self, l: LaneID) -> Vec<(CarID, Distance, Duration)> {
+        self.driving.get_lane_queue(self.time, l)
+    }
+
+    // From 0 to 1. For parking lanes, the fraction of spots filled; for other lanes, the fraction
+    // of the length covered by vehicles.
+    pub fn get_lane_occupancy(&self, l: LaneID, map: &Map) -> f64 {
+        if map.get_l(l).is_parking() {
+            self.parking.get_lane_occupancy(l).unwrap_or(0.0)
+        } else {
+            self.driving.get_lane_occupancy(l)
+        }
+    }
+
+    // Like get_lane_occupancy, for every lane at once. Empty lanes may be missing.
+    pub fn get_all_lane_occupancy(&self) -> BTreeMap<LaneID, f64> {
+        let mut results = self.driving.get_all_lane_occupancy();
+        results.extend(self.parking.get_all_lane_occupancy());
+        results
+    }
+
+    // (occupied, total) parking spots per road. Offstreet spots and lots count towards the road
+    // they're accessed from. Roads without any parking are missing.
+    pub fn parking_summary_by_road(&self, map: &Map) -> HashMap<RoadID, (usize, usize)> {
+        let road = |spot: ParkingSpot| match spot {
+            ParkingSpot::Onstreet(l, _) => map.get_l(l).parent,
+            _ => {
+                map.get_l(self.parking.spot_to_sidewalk_pos(spot, map).lane())
+                    .parent
+            }
+        };
+        let (filled, available) = self.parking.get_all_parking_spots();
+        let mut results: HashMap<RoadID, (usize, usize)> = HashMap::new();
+        for spot in filled {
+            let entry = results.entry(road(spot)).or_insert((0, 0));
+            entry.0 += 1;
+            entry.1 += 1;
+        }
+        for spot in available {
+            results.entry(road(spot)).or_insert((0, 0)).1 += 1;
+        }
+        results
+    }
+
     pub fn bldg_to_people(&self, b: BuildingID) -> Vec<PersonID> {
         self.trips.bldg_to_people(b)
     }
@@ -1171,6 +2049,44 @@ impl Sim {
 
 // Invasive debugging
 impl Sim {
+    // Spawns a car at exactly this position, without a scenario, and returns its ID. The car
+    // shows up as soon as the sim steps, unless there's no room there, which aborts the trip.
+    // Cars don't have an instantaneous speed in this model; instead, the car never crosses a lane
+    // faster than speed. Meant for focused tests of car-following and turn conflicts. Like any
+    // VehicleAppearing trip, pos must leave room for the whole car on the lane.
+    pub fn teleport_car(
+        &mut self,
+        mut spec: VehicleSpec,
+        pos: Position,
+        goal: DrivingGoal,
+        speed: Speed,
+        map: &Map,
+    ) -> CarID {
+        spec.max_speed = Some(speed);
+        let person = self
+            .random_person(Speed::miles_per_hour(3.0), vec![spec])
+            .id;
+        let car = self.get_person(person).vehicles[0].id;
+        let mut spawner = self.make_spawner();
+        spawner.schedule_trip(
+            self.get_person(person),
+            self.time,
+            TripSpec::VehicleAppearing {
+                start_pos: pos,
+                goal,
+                use_vehicle: car,
+                retry_if_no_room: false,
+                origin: None,
+                give_up_at: None,
+            },
+            TripEndpoint::Border(map.get_l(pos.lane()).src_i, None),
+            TripPurpose::Unknown,
+            map,
+        );
+        self.flush_spawner(spawner, map, &mut Timer::throwaway());
+        car
+    }
+
     pub fn kill_stuck_car(&mut self, id: CarID, map: &Map) {
         if let Some(trip) = self.agent_to_trip(AgentID::Car(id)) {
             let vehicle = self.driving.kill_stuck_car(
@@ -1194,6 +2110,159 @@ impl Sim {
         }
     }
 
+    // Call after editing the map in the middle of a simulation. Cars whose path ahead uses a lane
+    // they can't drive on anymore, or a turn that's gone, find another way to their goal. The
+    // ones that can't get there anymore are removed, and their trips are aborted. Returns how
+    // many were removed. Cars in the middle of a turn keep going and find a new way once they
+    // reach the end of the next lane; they're removed then if there isn't one.
+    pub fn handle_live_edits(&mut self, map: &Map) -> usize {
+        let failed = self.driving.reroute_after_edits(
+            self.time,
+            map,
+            &mut self.scheduler,
+            &mut self.intersections,
+        );
+        for id in &failed {
+            self.kill_stuck_car(*id, map);
+        }
+        failed.len()
+    }
+
+    // Cars waiting to turn for longer than this first try a different route. If there isn't one,
+    // they're removed and their trip is aborted. None, the default, means wait forever.
+    pub fn set_max_wait(&mut self, max_wait: Option<Duration>) {
+        self.max_wait = max_wait;
+        self.scheduler.cancel(Command::CheckForStuckCars);
+        if max_wait.is_some() {
+            self.scheduler.push(
+                self.time + STUCK_CHECK_FREQUENCY,
+                Command::CheckForStuckCars,
+            );
+        }
+    }
+
+    // Closes (or reopens) a lane at some point. Nobody can start a turn into a closed lane, but
+    // pathfinding doesn't know about it, so agents just queue up behind it. Closures at the same
+    // time happen in the order they were scheduled. A time that's already passed, like from a
+    // scenario instantiated partway through the day, happens right away.
+    pub fn schedule_lane_closure(&mut self, time: Time, l: LaneID, closed: bool) {
+        let time = time.max(self.time);
+        let idx = self
+            .lane_closures
+            .iter()
+            .position(|(t, _, _)| *t > time)
+            .unwrap_or_else(|| self.lane_closures.len());
+        self.lane_closures.insert(idx, (time, l, closed));
+        self.scheduler.cancel(Command::ChangeLaneClosures);
+        self.scheduler
+            .push(self.lane_closures[0].0, Command::ChangeLaneClosures);
+    }
+
+    pub fn is_lane_closed(&self, l: LaneID) -> bool {
+        self.intersections.is_lane_closed(l)
+    }
+
+    // When enabled, every driver or cyclist leaving through a border is replaced by a new one,
+    // entering from a random border and leaving through another. This keeps the number of
+    // vehicles steady, for soak testing over long runs. Trips that're aborted don't count. The
+    // choices come from a fixed seed, so runs are still reproducible. This is part of the
+    // savestate.
+    pub fn set_border_recycle(&mut self, enabled: bool) {
+        self.border_recycle = if enabled { Some(0) } else { None };
+    }
+
+    // Holds a traffic signal in one phase until clear_signal_override, like to find out if
+    // gridlock happens when some phase lasts too long. This is part of the savestate, but
+    // reset() drops it.
+    pub fn override_signal(&mut self, i: IntersectionID, phase: usize, map: &Map) {
+        self.intersections
+            .override_signal(self.time, i, phase, &mut self.scheduler, map);
+    }
+
+    pub fn clear_signal_override(&mut self, i: IntersectionID, map: &Map) {
+        self.intersections
+            .clear_signal_override(self.time, i, &mut self.scheduler, map);
+    }
+
+    // Start counting get_intersection_throughput from zero everywhere, like to compare two signal
+    // timings from the same point in a run.
+    pub fn reset_intersection_throughput(&mut self) {
+        self.intersections.reset_throughput();
+    }
+
+    // Makes every vehicle of this type move faster or slower, like testing what faster buses
+    // would do. Vehicles already moving pick it up on the next lane or turn. Set back to 1.0 to
+    // undo. This is part of the savestate.
+    pub fn set_vehicle_type_speed_multiplier(&mut self, vt: VehicleType, multiplier: f64) {
+        self.driving.set_speed_multiplier(vt, multiplier);
+    }
+
+    // Removes a moving or parked car entirely, instead of warping it somewhere like
+    // kill_stuck_car does. If the car was on a trip, the trip is aborted. Cars queued behind it
+    // can move on the next step. Buses can't be deleted; that raises an alert. Returns false if
+    // nothing was deleted.
+    pub fn delete_car(&mut self, id: CarID, map: &Map) -> bool {
+        if id.1 == VehicleType::Bus {
+            self.dispatch_events(
+                vec![Event::Alert(
+                    AlertLocation::Nil,
+                    format!("Can't delete {}; buses are tied to their route", id),
+                )],
+                map,
+            );
+            return false;
+        }
+        if self.driving.does_car_exist(id) {
+            let maybe_trip = self.agent_to_trip(AgentID::Car(id));
+            // A car that's parking has the spot reserved. One that's unparking already freed its
+            // spot when it started.
+            if let Some((spot, true)) = self.driving.get_parking_maneuver(id) {
+                self.parking.unreserve_spot(spot);
+            }
+            self.parking.forget_owner_bldg(id);
+            self.driving.kill_stuck_car(
+                id,
+                self.time,
+                map,
+                &mut self.scheduler,
+                &mut self.intersections,
+            );
+            if let Some(trip) = maybe_trip {
+                self.trips.abort_trip(
+                    self.time,
+                    trip,
+                    None,
+                    &mut self.parking,
+                    &mut self.scheduler,
+                    map,
+                );
+            }
+            return true;
+        }
+        if let Some(p) = self.parking.lookup_parked_car(id).cloned() {
+            // If the owner tries to use it later, their trip is aborted.
+            self.parking.remove_parked_car(p);
+            self.parking.forget_owner_bldg(id);
+            return true;
+        }
+        false
+    }
+
+    // Start recording SimEvents. This has some overhead, so it's off by default.
+    pub fn enable_event_log(&mut self) {
+        if self.event_log.is_none() {
+            self.event_log = Some(Vec::new());
+        }
+    }
+
+    // Everything logged since the last call, oldest first. Empty if the log isn't enabled.
+    pub fn take_events(&mut self) -> Vec<SimEvent> {
+        self.event_log
+            .as_mut()
+            .map(|log| std::mem::replace(log, Vec::new()))
+            .unwrap_or_else(Vec::new)
+    }
+
     pub fn clear_alerts(&mut self) -> Vec<(Time, AlertLocation, String)> {
         std::mem::replace(&mut self.analytics.alerts, Vec::new())
     }
@@ -1220,6 +2289,64 @@ impl Sim {
     }
 }
 
+pub struct AgentDescription {
+    pub id: AgentID,
+    // The lane or turn the agent is on. For bus passengers, wherever the bus is.
+    pub on: Traversable,
+    pub speed: Speed,
+    // The mode and destination of the current trip. Buses don't have a trip.
+    pub mode: Option<TripMode>,
+    pub destination: Option<TripEndpoint>,
+    // The turn the agent is waiting to start, if it's stuck at an intersection
+    pub waiting_for: Option<TurnID>,
+    // How long the current trip has been underway
+    pub elapsed: Option<Duration>,
+}
+
+// A snapshot of the whole simulation, meant to be recorded periodically during long runs
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MetricsRow {
+    pub time: Time,
+    pub active_cars: usize,
+    // Queued behind someone or at an intersection
+    pub cars_waiting: usize,
+    pub cars_searching_for_parking: usize,
+    pub peds: usize,
+    // Along a sidewalk or crosswalk
+    pub peds_walking: usize,
+    pub peds_waiting: usize,
+    // Over every car on the road. None if there aren't any.
+    pub mean_speed: Option<Speed>,
+    pub finished_trips: usize,
+    pub unfinished_trips: usize,
+}
+
+impl MetricsRow {
+    pub const CSV_HEADER: &'static str = "time_seconds,active_cars,cars_waiting,\
+                                          cars_searching_for_parking,peds,peds_walking,\
+                                          peds_waiting,mean_speed_mps,finished_trips,\
+                                          unfinished_trips";
+
+    // The columns match CSV_HEADER. A missing mean speed is left blank.
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{}",
+            self.time.inner_seconds(),
+            self.active_cars,
+            self.cars_waiting,
+            self.cars_searching_for_parking,
+            self.peds,
+            self.peds_walking,
+            self.peds_waiting,
+            self.mean_speed
+                .map(|s| s.inner_meters_per_second().to_string())
+                .unwrap_or_else(String::new),
+            self.finished_trips,
+            self.unfinished_trips
+        )
+    }
+}
+
 pub struct AgentProperties {
     // TODO Of this leg of the trip only!
     pub total_time: Duration,
@@ -1233,3 +2360,131 @@ pub struct AgentProperties {
     pub lanes_crossed: usize,
     pub total_lanes: usize,
 }
+
+// Both must be non-negative. 0 if both are 0.
+fn relative_change(x: f64, y: f64) -> f64 {
+    let max = x.max(y);
+    if max <= 0.0 {
+        return 0.0;
+    }
+    (x - y).abs() / max
+}
+
+// These need the montlake map, so they're ignored by default. Import it, then run with
+// `cargo test -- --ignored`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ScenarioGenerator;
+
+    fn load_montlake(timer: &mut Timer) -> (Map, Scenario) {
+        let map = Map::new(abstutil::path_map("montlake"), timer);
+        let scenario = ScenarioGenerator::small_run(&map).generate(
+            &map,
+            &mut XorShiftRng::from_seed([42; 16]),
+            timer,
+        );
+        (map, scenario)
+    }
+
+    fn run(map: &Map, scenario: &Scenario, opts: SimOptions, timer: &mut Timer) -> Sim {
+        let mut sim = Sim::new(map, opts, timer);
+        scenario.instantiate(&mut sim, map, &mut XorShiftRng::from_seed([42; 16]), timer);
+        sim.timed_step(map, Duration::minutes(10), &mut None, timer);
+        sim
+    }
+
+    // Keep stepping for up to an hour, until some car pulls into (or out of) a parking spot
+    fn find_car_maneuvering(sim: &mut Sim, map: &Map, parking: bool) -> (CarID, ParkingSpot) {
+        let end = sim.time() + Duration::hours(1);
+        while sim.time() < end {
+            for c in sim.driving.all_cars() {
+                if let Some((spot, is_parking)) = sim.driving.get_parking_maneuver(c) {
+                    if is_parking == parking {
+                        return (c, spot);
+                    }
+                }
+            }
+            sim.tiny_step(map, &mut None);
+        }
+        panic!("No car parked or unparked by {}", sim.time());
+    }
+
+    #[test]
+    #[ignore]
+    fn test_determinism() {
+        let mut timer = Timer::throwaway();
+        let (map, scenario) = load_montlake(&mut timer);
+        let sim1 = run(&map, &scenario, SimOptions::new("test"), &mut timer);
+        let sim2 = run(&map, &scenario, SimOptions::new("test"), &mut timer);
+        assert_eq!(sim1.assert_equal(&sim2), Ok(()));
+    }
+
+    #[test]
+    #[ignore]
+    fn test_reset() {
+        let mut timer = Timer::throwaway();
+        let (map, scenario) = load_montlake(&mut timer);
+        let mut sim = Sim::new(&map, SimOptions::new("test"), &mut timer);
+        scenario.instantiate(
+            &mut sim,
+            &map,
+            &mut XorShiftRng::from_seed([42; 16]),
+            &mut timer,
+        );
+        sim.set_reset_point();
+        sim.timed_step(&map, Duration::minutes(10), &mut None, &mut timer);
+        let first = sim.clone();
+
+        sim.reset(&map);
+        sim.timed_step(&map, Duration::minutes(10), &mut None, &mut timer);
+        assert_eq!(first.assert_equal(&sim), Ok(()));
+    }
+
+    #[test]
+    #[ignore]
+    fn test_delete_parking_car() {
+        let mut timer = Timer::throwaway();
+        let (map, scenario) = load_montlake(&mut timer);
+        let mut sim = run(&map, &scenario, SimOptions::new("test"), &mut timer);
+        let (car, spot) = find_car_maneuvering(&mut sim, &map, true);
+        assert!(!sim.parking.is_free(spot));
+
+        assert!(sim.delete_car(car, &map));
+        assert!(!sim.driving.does_car_exist(car));
+        assert!(sim.parking.is_free(spot));
+        sim.timed_step(&map, Duration::minutes(10), &mut None, &mut timer);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_delete_unparking_car() {
+        let mut timer = Timer::throwaway();
+        let (map, scenario) = load_montlake(&mut timer);
+        let mut sim = run(&map, &scenario, SimOptions::new("test"), &mut timer);
+        let (car, spot) = find_car_maneuvering(&mut sim, &map, false);
+
+        assert!(sim.delete_car(car, &map));
+        assert!(!sim.driving.does_car_exist(car));
+        assert!(sim.parking.is_free(spot));
+        sim.timed_step(&map, Duration::minutes(10), &mut None, &mut timer);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_savestate() {
+        // Save partway through, while plenty of trips are still scheduled for the future.
+        let mut timer = Timer::throwaway();
+        let (map, scenario) = load_montlake(&mut timer);
+        let mut sim = run(&map, &scenario, SimOptions::new("test"), &mut timer);
+        let path = format!("{}/test_savestate.bin", std::env::temp_dir().display());
+        sim.save_savestate(path.clone());
+        let mut loaded = Sim::load_savestate(path.clone(), &map, &mut timer).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(sim.assert_equal(&loaded), Ok(()));
+
+        sim.timed_step(&map, Duration::minutes(10), &mut None, &mut timer);
+        loaded.timed_step(&map, Duration::minutes(10), &mut None, &mut timer);
+        assert_eq!(sim.assert_equal(&loaded), Ok(()));
+    }
+}
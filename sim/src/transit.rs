@@ -62,16 +62,31 @@ pub struct TransitSimState {
         deserialize_with = "deserialize_btreemap"
     )]
     peds_waiting: BTreeMap<BusStopID, Vec<(PedestrianID, BusRouteID, BusStopID, Time)>>,
+    // Where everybody currently riding a bus got on
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    boarded_at: BTreeMap<PersonID, BusStopID>,
 
     events: Vec<Event>,
 }
 
+// Where somebody using transit is on their way
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TransitRiderState {
+    WalkingToStop,
+    WaitingAtStop,
+    Riding,
+}
+
 impl TransitSimState {
     pub fn new() -> TransitSimState {
         TransitSimState {
             buses: BTreeMap::new(),
             routes: BTreeMap::new(),
             peds_waiting: BTreeMap::new(),
+            boarded_at: BTreeMap::new(),
             events: Vec::new(),
         }
     }
@@ -168,6 +183,7 @@ impl TransitSimState {
                 let mut still_riding = Vec::new();
                 for (person, stop2) in bus.passengers.drain(..) {
                     if stop1 == stop2 {
+                        self.boarded_at.remove(&person);
                         trips.person_left_bus(now, person, bus.car, map, scheduler);
                     } else {
                         still_riding.push((person, stop2));
@@ -199,6 +215,7 @@ impl TransitSimState {
                             TripPhaseType::RidingBus(route, stop1, bus.car),
                         ));
                         bus.passengers.push((person, stop2));
+                        self.boarded_at.insert(person, stop1);
                     } else {
                         still_waiting.push((ped, route, stop2, started_waiting));
                     }
@@ -250,6 +267,7 @@ impl TransitSimState {
                             .unwrap()
                             .passengers
                             .push((person, stop2));
+                        self.boarded_at.insert(person, stop1);
                         self.events.push(Event::TripPhaseStarting(
                             trip,
                             person,
@@ -282,6 +300,17 @@ impl TransitSimState {
         self.events.drain(..).collect()
     }
 
+    pub fn is_ped_waiting(&self, ped: PedestrianID, stop: BusStopID) -> bool {
+        self.peds_waiting
+            .get(&stop)
+            .map(|peds| peds.iter().any(|(p, _, _, _)| *p == ped))
+            .unwrap_or(false)
+    }
+
+    pub fn get_boarding_stop(&self, person: PersonID) -> Option<BusStopID> {
+        self.boarded_at.get(&person).cloned()
+    }
+
     pub fn get_passengers(&self, bus: CarID) -> &Vec<(PersonID, BusStopID)> {
         &self.buses[&bus].passengers
     }
@@ -1,8 +1,8 @@
 use crate::{
     AgentID, AlertLocation, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal, Event,
     OffMapLocation, OrigPersonID, ParkedCar, ParkingSimState, ParkingSpot, PedestrianID, PersonID,
-    Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState, TripID, TripPhaseType, TripSpec,
-    Vehicle, VehicleSpec, VehicleType, WalkingSimState,
+    Scheduler, SidewalkPOI, SidewalkSpot, TransitRiderState, TransitSimState, TripID,
+    TripPhaseType, TripPurpose, TripSpec, Vehicle, VehicleSpec, VehicleType, WalkingSimState,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
 use geom::{Distance, Duration, Speed, Time};
@@ -49,6 +49,7 @@ impl TripManager {
         &mut self,
         id: PersonID,
         orig_id: Option<OrigPersonID>,
+        provenance: Option<String>,
         ped_speed: Speed,
         vehicle_specs: Vec<VehicleSpec>,
     ) {
@@ -63,6 +64,7 @@ impl TripManager {
         self.people.push(Person {
             id,
             orig_id,
+            provenance,
             trips: Vec::new(),
             // The first new_trip will set this properly.
             state: PersonState::OffMap,
@@ -75,7 +77,7 @@ impl TripManager {
     }
     pub fn random_person(&mut self, ped_speed: Speed, vehicle_specs: Vec<VehicleSpec>) -> &Person {
         let id = PersonID(self.people.len());
-        self.new_person(id, None, ped_speed, vehicle_specs);
+        self.new_person(id, None, None, ped_speed, vehicle_specs);
         self.get_person(id).unwrap()
     }
 
@@ -89,6 +91,7 @@ impl TripManager {
         &mut self,
         person: PersonID,
         departure: Time,
+        purpose: TripPurpose,
         start: TripEndpoint,
         mode: TripMode,
         legs: Vec<TripLeg>,
@@ -98,6 +101,7 @@ impl TripManager {
         // TODO Make sure the legs constitute a valid state machine.
 
         let id = TripID(self.trips.len());
+        let remote = matches!(legs.last(), Some(TripLeg::Remote(_)));
         let end = match legs.last() {
             Some(TripLeg::Walk(ref spot)) => match spot.connection {
                 SidewalkPOI::Building(b) => TripEndpoint::Bldg(b),
@@ -117,11 +121,14 @@ impl TripManager {
             id,
             person,
             departure,
-            started: false,
+            started_at: None,
             finished_at: None,
             total_blocked_time: Duration::ZERO,
             aborted: false,
+            cancelled: false,
+            remote,
             mode,
+            purpose,
             legs: VecDeque::from(legs),
             start,
             end,
@@ -196,6 +203,7 @@ impl TripManager {
                     self.events.push(Event::TripFinished {
                         trip: trip.id,
                         mode: trip.mode,
+                        purpose: trip.purpose,
                         total_time: now - trip.departure,
                         blocked_time: trip.total_blocked_time,
                     });
@@ -430,6 +438,7 @@ impl TripManager {
         self.events.push(Event::TripFinished {
             trip: trip.id,
             mode: trip.mode,
+            purpose: trip.purpose,
             total_time: now - trip.departure,
             blocked_time: trip.total_blocked_time,
         });
@@ -578,6 +587,7 @@ impl TripManager {
         self.events.push(Event::TripFinished {
             trip: trip.id,
             mode: trip.mode,
+            purpose: trip.purpose,
             total_time: now - trip.departure,
             blocked_time: trip.total_blocked_time,
         });
@@ -621,6 +631,7 @@ impl TripManager {
         self.events.push(Event::TripFinished {
             trip: trip.id,
             mode: trip.mode,
+            purpose: trip.purpose,
             total_time: now - trip.departure,
             blocked_time: trip.total_blocked_time,
         });
@@ -658,6 +669,7 @@ impl TripManager {
         self.events.push(Event::TripFinished {
             trip: trip.id,
             mode: trip.mode,
+            purpose: trip.purpose,
             total_time: now - trip.departure,
             blocked_time: trip.total_blocked_time,
         });
@@ -756,10 +768,75 @@ impl TripManager {
         self.person_finished_trip(now, person, parking, scheduler, map);
     }
 
+    // For a trip that never got started on the map, like a metered spawn that gave up waiting.
+    // The person stays wherever they were and moves on to their next trip.
+    pub fn cancel_trip(
+        &mut self,
+        now: Time,
+        id: TripID,
+        parking: &mut ParkingSimState,
+        scheduler: &mut Scheduler,
+        map: &Map,
+    ) {
+        let trip = &mut self.trips[id.0];
+        self.unfinished_trips -= 1;
+        trip.cancelled = true;
+        self.events.push(Event::TripCancelled(trip.id));
+        let person = trip.person;
+        // The only trips cancelled so far start off-map
+        if let TripEndpoint::Border(_, _) = trip.start {
+            self.people[person.0].state = PersonState::OffMap;
+        }
+        self.person_finished_trip(now, person, parking, scheduler, map);
+    }
+
     pub fn active_agents(&self) -> Vec<AgentID> {
         self.active_trip_mode.keys().cloned().collect()
     }
 
+    // (agent, route, boarding stop, alighting stop, state) for every active trip currently
+    // involving transit
+    pub fn active_transit_trips(
+        &self,
+        transit: &TransitSimState,
+    ) -> Vec<(AgentID, BusRouteID, BusStopID, BusStopID, TransitRiderState)> {
+        let mut results = Vec::new();
+        for (agent, trip) in &self.active_trip_mode {
+            let trip = &self.trips[trip.0];
+            match agent {
+                AgentID::Pedestrian(ped) => {
+                    if let (Some(TripLeg::Walk(spot)), Some(TripLeg::RideBus(route, stop2))) =
+                        (trip.legs.get(0), trip.legs.get(1))
+                    {
+                        if let SidewalkPOI::BusStop(stop1) = spot.connection {
+                            let state = if transit.is_ped_waiting(*ped, stop1) {
+                                TransitRiderState::WaitingAtStop
+                            } else {
+                                TransitRiderState::WalkingToStop
+                            };
+                            results.push((*agent, *route, stop1, *stop2, state));
+                        }
+                    }
+                }
+                AgentID::BusPassenger(person, _) => {
+                    if let Some(TripLeg::RideBus(route, stop2)) = trip.legs.get(0) {
+                        if let Some(stop1) = transit.get_boarding_stop(*person) {
+                            results.push((
+                                *agent,
+                                *route,
+                                stop1,
+                                *stop2,
+                                TransitRiderState::Riding,
+                            ));
+                        }
+                    }
+                }
+                AgentID::Car(_) => {}
+            }
+        }
+        results
+    }
+
     pub fn get_active_trips(&self) -> Vec<TripID> {
         self.active_trip_mode.values().cloned().collect()
     }
@@ -776,7 +853,10 @@ impl TripManager {
         if trip.aborted {
             return TripResult::TripAborted;
         }
-        if !trip.started {
+        if trip.cancelled {
+            return TripResult::TripCancelled;
+        }
+        if trip.started_at.is_none() {
             return TripResult::TripNotStarted;
         }
 
@@ -847,6 +927,9 @@ impl TripManager {
     pub fn is_done(&self) -> bool {
         self.unfinished_trips == 0
     }
+    pub fn num_trips_remaining(&self) -> usize {
+        self.unfinished_trips
+    }
 
     pub fn collect_events(&mut self) -> Vec<Event> {
         std::mem::replace(&mut self.events, Vec::new())
@@ -856,10 +939,27 @@ impl TripManager {
         let t = &self.trips[id.0];
         (t.departure, t.start.clone(), t.end.clone(), t.mode)
     }
+    // Did the trip finish by driving or biking off the map through a border? Aborted and remote
+    // trips don't count.
+    pub fn vehicle_finished_at_border(&self, id: TripID) -> bool {
+        let t = &self.trips[id.0];
+        t.finished_at.is_some()
+            && !t.remote
+            && (t.mode == TripMode::Drive || t.mode == TripMode::Bike)
+            && matches!(t.end, TripEndpoint::Border(_, _))
+    }
     pub fn finished_trip_time(&self, id: TripID) -> Option<(Duration, Duration)> {
         let t = &self.trips[id.0];
         Some((t.finished_at? - t.departure, t.total_blocked_time))
     }
+    // How long an ongoing trip has been underway
+    pub fn trip_elapsed(&self, id: TripID, now: Time) -> Option<Duration> {
+        let t = &self.trips[id.0];
+        if t.finished_at.is_some() || t.aborted || t.cancelled {
+            return None;
+        }
+        Some(now - t.started_at?)
+    }
 
     pub fn bldg_to_people(&self, b: BuildingID) -> Vec<PersonID> {
         let mut people = Vec::new();
@@ -881,6 +981,9 @@ impl TripManager {
     pub fn trip_to_person(&self, id: TripID) -> PersonID {
         self.trips[id.0].person
     }
+    pub fn trip_purpose(&self, id: TripID) -> TripPurpose {
+        self.trips[id.0].purpose
+    }
 
     fn person_finished_trip(
         &mut self,
@@ -943,7 +1046,7 @@ impl TripManager {
             ));
             return;
         }
-        self.trips[trip.0].started = true;
+        self.trips[trip.0].started_at = Some(now);
 
         match spec {
             TripSpec::VehicleAppearing {
@@ -952,6 +1055,7 @@ impl TripManager {
                 retry_if_no_room,
                 use_vehicle,
                 origin,
+                give_up_at,
             } => {
                 assert_eq!(person.state, PersonState::OffMap);
                 self.events.push(Event::PersonEntersMap(
@@ -968,15 +1072,10 @@ impl TripManager {
                 if let Some(router) =
                     maybe_path.and_then(|path| goal.make_router(path, map, vehicle.vehicle_type))
                 {
-                    scheduler.push(
-                        now,
-                        Command::SpawnCar(
-                            CreateCar::for_appearing(
-                                vehicle, start_pos, router, req, trip, person.id,
-                            ),
-                            retry_if_no_room,
-                        ),
-                    );
+                    let mut create_car =
+                        CreateCar::for_appearing(vehicle, start_pos, router, req, trip, person.id);
+                    create_car.give_up_at = give_up_at;
+                    scheduler.push(now, Command::SpawnCar(create_car, retry_if_no_room));
                 } else {
                     self.events.push(Event::Alert(
                         AlertLocation::Person(person.id),
@@ -1246,12 +1345,19 @@ impl TripManager {
 struct Trip {
     id: TripID,
     departure: Time,
-    started: bool,
+    // Could be after departure, if the person was still busy with a previous trip
+    started_at: Option<Time>,
     finished_at: Option<Time>,
     total_blocked_time: Duration,
     aborted: bool,
+    // A metered spawn that gave up before it could enter the map. Unlike aborted, nothing went
+    // wrong.
+    cancelled: bool,
+    // Never on the map at all
+    remote: bool,
     legs: VecDeque<TripLeg>,
     mode: TripMode,
+    purpose: TripPurpose,
     start: TripEndpoint,
     end: TripEndpoint,
     person: PersonID,
@@ -1328,6 +1434,30 @@ pub enum TripLeg {
     Remote(OffMapLocation),
 }
 
+// Why somebody is making a trip. This doesn't affect how the trip is simulated; it's just carried
+// along for breaking down results, like survey categories.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, PartialOrd, Ord)]
+pub enum TripPurpose {
+    // Old scenarios and generated trips that don't say
+    Unknown,
+    Home,
+    Work,
+    School,
+    Escort,
+    PersonalBusiness,
+    Shopping,
+    Meal,
+    Social,
+    Recreation,
+    Medical,
+}
+
+impl Default for TripPurpose {
+    fn default() -> TripPurpose {
+        TripPurpose::Unknown
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, PartialOrd, Ord)]
 pub enum TripMode {
     Walk,
@@ -1402,6 +1532,7 @@ pub enum TripResult<T> {
     TripDoesntExist,
     TripNotStarted,
     TripAborted,
+    TripCancelled,
     RemoteTrip,
 }
 
@@ -1421,6 +1552,7 @@ impl<T> TripResult<T> {
             TripResult::TripDoesntExist => TripResult::TripDoesntExist,
             TripResult::TripNotStarted => TripResult::TripNotStarted,
             TripResult::TripAborted => TripResult::TripAborted,
+            TripResult::TripCancelled => TripResult::TripCancelled,
             TripResult::RemoteTrip => TripResult::RemoteTrip,
         }
     }
@@ -1430,6 +1562,8 @@ impl<T> TripResult<T> {
 pub struct Person {
     pub id: PersonID,
     pub orig_id: Option<OrigPersonID>,
+    // What generated this person, for debugging
+    pub provenance: Option<String>,
     pub trips: Vec<TripID>,
     // TODO home
     pub state: PersonState,
@@ -1457,6 +1591,54 @@ pub enum PersonState {
 }
 
 impl TripEndpoint {
+    // Checks that SpawnTrip::new can use this as the start or end of a trip with this mode,
+    // instead of panicking.
+    pub(crate) fn check(&self, mode: TripMode, starting: bool, map: &Map) -> Result<(), String> {
+        let i = match self {
+            TripEndpoint::Bldg(b) => {
+                if map.maybe_get_b(*b).is_none() {
+                    return Err(format!("{} doesn't exist", b));
+                }
+                return Ok(());
+            }
+            TripEndpoint::Border(i, _) => match map.maybe_get_i(*i) {
+                Some(i) => i,
+                None => {
+                    return Err(format!("{} doesn't exist", i));
+                }
+            },
+        };
+        let ok = match (mode, starting) {
+            (TripMode::Drive, true) | (TripMode::Bike, true) => i.some_outgoing_road(map).is_some(),
+            (TripMode::Drive, false) | (TripMode::Bike, false) => {
+                let constraints = if mode == TripMode::Drive {
+                    PathConstraints::Car
+                } else {
+                    PathConstraints::Bike
+                };
+                i.some_incoming_road(map)
+                    .and_then(|dr| DrivingGoal::end_at_border(dr, constraints, None, map))
+                    .is_some()
+            }
+            (TripMode::Walk, true) | (TripMode::Transit, true) => {
+                SidewalkSpot::start_at_border(i.id, None, map).is_some()
+            }
+            (TripMode::Walk, false) | (TripMode::Transit, false) => {
+                SidewalkSpot::end_at_border(i.id, None, map).is_some()
+            }
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(format!(
+                "{:?} trips can't {} at {}",
+                mode,
+                if starting { "start" } else { "end" },
+                i.id
+            ))
+        }
+    }
+
     pub(crate) fn start_sidewalk_spot(&self, map: &Map) -> SidewalkSpot {
         match self {
             TripEndpoint::Bldg(b) => SidewalkSpot::building(*b, map),